@@ -0,0 +1,667 @@
+use std::collections::HashMap;
+use std::fmt;
+use crate::lexer::Span;
+use crate::parser::{BinaryOperator, Expression, ExpressionKind, Pattern, Statement};
+use crate::runtime::{Runtime, Value};
+use crate::jadeErrors::{
+    appendError, argumentCountError, enumNotFoundError, fieldAccessError, fieldNotFoundError, functionNotFoundError, inferNullError,
+    missingFieldError, nullOperandError, operandTypesError, payloadCountError, structNotFoundError, suggest,
+    typeMismatchError, typeNotFoundError, unsupportedOperatorError, variableNotFoundError, variantNotFoundError,
+    JadeError,
+};
+
+/// The type of an expression as far as it can be told without running
+/// anything. Integers and floats carry their width when it matters for
+/// assigning them, `None` means any width goes (plain literals and
+/// `int`/`float` variables, which the runtime fits to the target).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Int(Option<String>),
+    Float(Option<String>),
+    Bool,
+    String,
+    Null,
+    Named(String), // A struct or an enum
+    Nullable(Box<Type>),
+    Any,           // `$` and `@`, or anything we can't tell
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Int(width) => write!(f, "{}", width.as_deref().unwrap_or("int")),
+            Type::Float(width) => write!(f, "{}", width.as_deref().unwrap_or("float")),
+            Type::Bool => write!(f, "bool"),
+            Type::String => write!(f, "string"),
+            Type::Null => write!(f, "null"),
+            Type::Named(name) => write!(f, "{}", name),
+            Type::Nullable(inner) => write!(f, "{}?", inner),
+            Type::Any => write!(f, "$"),
+        }
+    }
+}
+
+impl Type {
+    /// The type a literal value has.
+    pub fn ofValue(value: &Value) -> Type {
+        match value {
+            // Plain literals are int64 and fit any width that holds them
+            Value::Int64(_) => Type::Int(None),
+            Value::Int8(_) => Type::Int(Some("int8".into())),
+            Value::UInt8(_) => Type::Int(Some("uint8".into())),
+            Value::Int16(_) => Type::Int(Some("int16".into())),
+            Value::UInt16(_) => Type::Int(Some("uint16".into())),
+            Value::Int32(_) => Type::Int(Some("int32".into())),
+            Value::UInt32(_) => Type::Int(Some("uint32".into())),
+            Value::UInt64(_) => Type::Int(Some("uint64".into())),
+            Value::Float32(_) => Type::Float(Some("float32".into())),
+            Value::Float(_) | Value::Float64(_) => Type::Float(None),
+            Value::Bool(_) => Type::Bool,
+            Value::String(_) => Type::String,
+            Value::Null => Type::Null,
+            Value::Struct(name, _) | Value::Enum(name, _, _) => Type::Named(name.clone()),
+            _ => Type::Any,
+        }
+    }
+
+    // Maybe-null values are checked as what they hold when not null,
+    // the runtime catches the null
+    fn nonNull(&self) -> &Type {
+        match self {
+            Type::Nullable(inner) => inner,
+            other => other,
+        }
+    }
+
+    /// The type a variable declared without one gets from its value.
+    /// Numbers lose their width like they do in `int` and `float`, and
+    /// maybe-null values count as what they are when not null. Null on
+    /// its own doesn't say anything, so there's nothing to infer.
+    pub fn inferred(&self) -> Option<Type> {
+        match self {
+            Type::Null => None,
+            Type::Int(_) => Some(Type::Int(None)),
+            Type::Float(_) => Some(Type::Float(None)),
+            Type::Nullable(inner) => inner.inferred(),
+            other => Some(other.clone()),
+        }
+    }
+
+    fn isNumber(&self) -> bool {
+        matches!(self, Type::Int(_) | Type::Float(_))
+    }
+
+    /// Whether a value of this type can be stored in `target`, following
+    /// what coerce_to_type accepts.
+    pub fn fits(&self, target: &Type) -> bool {
+        match (self, target) {
+            (Type::Any, _) | (_, Type::Any) => true,
+            (Type::Null, Type::Nullable(_)) => true,
+            (value, Type::Nullable(inner)) => value.nonNull().fits(inner),
+            (Type::Nullable(inner), target) => inner.fits(target),
+            (Type::Int(width), Type::Int(target)) | (Type::Float(width), Type::Float(target)) => {
+                target.is_none() || width.is_none() || width == target
+            }
+            (value, target) => value == target,
+        }
+    }
+
+    // The type of something that is either `self` or `other`
+    fn join(self, other: Type) -> Type {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Type::Any, _) | (_, Type::Any) => Type::Any,
+            (Type::Null, Type::Nullable(t)) | (Type::Nullable(t), Type::Null) => Type::Nullable(t),
+            (Type::Null, t) | (t, Type::Null) => Type::Nullable(Box::new(t)),
+            (Type::Int(_), Type::Int(_)) => Type::Int(None),
+            (Type::Float(_), Type::Float(_)) => Type::Float(None),
+            _ => Type::Any,
+        }
+    }
+}
+
+/// Checks the types in the whole program before anything runs:
+/// values against the types they're declared or assigned as, the
+/// operands of every binary operator, and that every variable, struct,
+/// enum and field used exists. Declarations are followed in order like
+/// the runtime does, and every problem is reported, not just the first.
+pub fn checkTypes(code: &[Statement]) -> Result<(), Vec<JadeError>> {
+    checkTypesAfter(code, &Runtime::new())
+}
+
+/// Checks code that runs after what's already in `runtime`, so the
+/// globals, structs and enums it declared are known.
+pub fn checkTypesAfter(code: &[Statement], runtime: &Runtime) -> Result<(), Vec<JadeError>> {
+    let mut checker = Checker {
+        structs: runtime.structs.clone(),
+        enums: runtime.enums.clone(),
+        functions: runtime.signatures(),
+        ..Checker::default()
+    };
+    for (name, global) in runtime.names.iter().zip(&runtime.globals) {
+        if let Some((_, declared)) = global {
+            let known = checker.declaredType(declared).unwrap_or(Type::Any);
+            checker.variables.insert(name.clone(), known);
+        }
+    }
+    for statement in code {
+        checker.statement(statement);
+    }
+    if checker.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(checker.errors)
+    }
+}
+
+#[derive(Default)]
+struct Checker {
+    variables: HashMap<String, Type>,
+    structs: HashMap<String, Vec<(String, String)>>,
+    enums: HashMap<String, Vec<(String, Vec<String>)>>,
+    functions: HashMap<String, Vec<String>>, // The argument types of each function
+    scopes: Vec<HashMap<String, Type>>, // Names bound by `match` arms
+    errors: Vec<JadeError>,
+}
+
+impl Checker {
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::VarDec { name, datatype, value, span, .. } => {
+                let found = self.expression(value);
+                let declared = match datatype {
+                    Some(datatype) => self.declaredType(datatype).map_err(|e| e.at(span.clone())),
+                    None => found.inferred().ok_or_else(|| inferNullError(name).at(value.span.clone())),
+                };
+                match declared {
+                    Ok(declared) => {
+                        self.expect(&found, &declared, value);
+                        self.variables.insert(name.clone(), declared);
+                    }
+                    Err(error) => {
+                        self.errors.push(error);
+                        self.variables.insert(name.clone(), Type::Any);
+                    }
+                }
+            }
+
+            Statement::StructDec { name, fields, .. } => {
+                self.structs.insert(name.clone(), fields.clone());
+            }
+
+            Statement::EnumDec { name, variants, .. } => {
+                self.enums.insert(name.clone(), variants.clone());
+            }
+
+            Statement::VarSet { name, value, span, .. } => {
+                let found = self.expression(value);
+                match self.variable(name) {
+                    Ok(declared) => self.expect(&found, &declared, value),
+                    Err(error) => self.errors.push(error.at(span.clone())),
+                }
+            }
+
+            Statement::FieldSet { name, path, value, span, .. } => {
+                let found = self.expression(value);
+                match self.variable(name).and_then(|t| self.fieldPathType(t, path)) {
+                    Ok(declared) => self.expect(&found, &declared, value),
+                    Err(error) => self.errors.push(error.at(span.clone())),
+                }
+            }
+
+            Statement::Print { values, target, .. } => {
+                for value in values {
+                    self.expression(value);
+                }
+                if let Some(target) = target {
+                    let found = self.expression(target);
+                    self.expect(&found, &Type::String, target);
+                }
+            }
+
+            // Variables take anything if they hold an array or a string,
+            // anything else is the name of a file
+            Statement::Append { value, target, .. } => {
+                self.expression(value);
+                let found = self.expression(target);
+                match (&target.kind, found.nonNull()) {
+                    (ExpressionKind::Variable { .. }, Type::String | Type::Any) => {}
+                    (ExpressionKind::Variable { .. }, other) => {
+                        self.errors.push(appendError(&other.to_string()).at(target.span.clone()));
+                    }
+                    _ => self.expect(&found, &Type::String, target),
+                }
+            }
+
+            // A line of input runs out, a file doesn't
+            Statement::Read { name, source, span, .. } => {
+                let found = match source {
+                    Some(source) => {
+                        let found = self.expression(source);
+                        self.expect(&found, &Type::String, source);
+                        Type::String
+                    }
+                    None => Type::Nullable(Box::new(Type::String)),
+                };
+                match self.variable(name) {
+                    Ok(declared) if !found.fits(&declared) => {
+                        self.errors.push(typeMismatchError(&declared.to_string(), &found.to_string()).at(span.clone()));
+                    }
+                    Ok(_) => {}
+                    Err(error) => self.errors.push(error.at(span.clone())),
+                }
+            }
+
+            Statement::Expression { value, .. } => {
+                self.expression(value);
+            }
+
+            Statement::If { condition, .. } |
+            Statement::Unless { condition, .. } |
+            Statement::While { condition, .. } |
+            Statement::Until { condition, .. } => {
+                self.condition(condition);
+                for inner in statement.children() {
+                    self.statement(inner);
+                }
+            }
+
+            Statement::For { init, condition, step, body, .. } => {
+                if let Some(init) = init {
+                    self.statement(init);
+                }
+                if let Some(condition) = condition {
+                    self.condition(condition);
+                }
+                for inner in body {
+                    self.statement(inner);
+                }
+                if let Some(step) = step {
+                    self.statement(step);
+                }
+            }
+        }
+    }
+
+    // Conditions have to be bools, nothing is truthy
+    fn condition(&mut self, condition: &Expression) {
+        let found = self.expression(condition);
+        self.expect(&found, &Type::Bool, condition);
+    }
+
+    // Reports `found` not fitting where `expected` is needed
+    fn expect(&mut self, found: &Type, expected: &Type, expr: &Expression) {
+        if !found.fits(expected) {
+            self.errors.push(typeMismatchError(&expected.to_string(), &found.to_string()).at(expr.span.clone()));
+        }
+    }
+
+    // The type of an expression. Anything wrong inside it is recorded
+    // and the broken part counts as `Any`, so one mistake doesn't cause
+    // a pile of others.
+    fn expression(&mut self, expr: &Expression) -> Type {
+        match self.expressionKind(expr) {
+            Ok(found) => found,
+            Err(error) => {
+                self.errors.push(error.at(expr.span.clone()));
+                Type::Any
+            }
+        }
+    }
+
+    fn expressionKind(&mut self, expr: &Expression) -> Result<Type, JadeError> {
+        let found = match &expr.kind {
+            ExpressionKind::Number(value) => Type::ofValue(value),
+            ExpressionKind::String(_) => Type::String,
+            ExpressionKind::Boolean(_) => Type::Bool,
+            ExpressionKind::Null => Type::Null,
+            ExpressionKind::Variable { name, .. } => self.variable(name)?,
+
+            ExpressionKind::StructLiteral { name, fields } => {
+                let declared = self.structs.get(name).cloned().ok_or_else(|| self.unknownType(structNotFoundError(name), name))?;
+                for (field, value) in fields {
+                    let found = self.expression(value);
+                    match declared.iter().find(|(f, _)| f == field) {
+                        Some((_, fieldType)) => {
+                            if let Ok(fieldType) = self.declaredType(fieldType) {
+                                self.expect(&found, &fieldType, value);
+                            }
+                        }
+                        None => {
+                            let error = suggest(fieldNotFoundError(name, field), field, declared.iter().map(|(f, _)| f));
+                            self.errors.push(error.at(value.span.clone()));
+                        }
+                    }
+                }
+
+                // Nullable fields can be left out
+                let missing = declared
+                    .iter()
+                    .find(|(field, fieldType)| !fieldType.ends_with('?') && !fields.iter().any(|(f, _)| f == field));
+                if let Some((field, _)) = missing {
+                    return Err(missingFieldError(name, field));
+                }
+                Type::Named(name.clone())
+            }
+
+            ExpressionKind::FieldAccess { object, field, optional } => {
+                let objectType = self.expression(object);
+                let fieldType = self.fieldPathType(objectType.clone(), std::slice::from_ref(field))?;
+                match objectType {
+                    Type::Nullable(_) if *optional => fieldType.join(Type::Null),
+                    _ => fieldType,
+                }
+            }
+
+            ExpressionKind::EnumVariant { enumName, variant, payload } => {
+                let types = self.variantTypes(enumName, variant, payload.len())?;
+                for (value, valueType) in payload.iter().zip(&types) {
+                    let found = self.expression(value);
+                    if let Ok(valueType) = self.declaredType(valueType) {
+                        self.expect(&found, &valueType, value);
+                    }
+                }
+                Type::Named(enumName.clone())
+            }
+
+            // What a function gives back is only known once it runs
+            ExpressionKind::Call { name, args } => {
+                let params = self.functions.get(name).cloned().ok_or_else(|| {
+                    suggest(functionNotFoundError(name), name, self.functions.keys())
+                })?;
+                if params.len() != args.len() {
+                    return Err(argumentCountError(name, params.len(), args.len()));
+                }
+                for (arg, param) in args.iter().zip(&params) {
+                    let found = self.expression(arg);
+                    if let Ok(param) = self.declaredType(param) {
+                        self.expect(&found, &param, arg);
+                    }
+                }
+                Type::Any
+            }
+
+            ExpressionKind::Match { subject, arms } => {
+                let subjectType = self.expression(subject);
+                let mut result: Option<Type> = None;
+                for (pattern, arm) in arms {
+                    let mut bindings: HashMap<String, Type> = HashMap::new();
+                    self.bindPattern(pattern, &subjectType, &expr.span, &mut bindings);
+                    self.scopes.push(bindings);
+                    let armType = self.expression(arm);
+                    self.scopes.pop();
+                    result = Some(match result {
+                        Some(result) => result.join(armType),
+                        None => armType,
+                    });
+                }
+                result.unwrap_or(Type::Any)
+            }
+
+            // `??` is the right side's type when the left is null
+            ExpressionKind::BinaryOp { op: BinaryOperator::NullCoalesce, left, right } => {
+                let left = self.expression(left);
+                let right = self.expression(right);
+                match left {
+                    Type::Null => right,
+                    Type::Nullable(inner) => inner.join(right),
+                    other => other,
+                }
+            }
+
+            ExpressionKind::BinaryOp { op, left, right } => {
+                let left = self.expression(left);
+                let right = self.expression(right);
+                binaryType(op, &left, &right)?
+            }
+        };
+        Ok(found)
+    }
+
+    // The names a pattern binds and their types. Variants have to exist
+    // and take as many values as the pattern gives them.
+    fn bindPattern(&mut self, pattern: &Pattern, subject: &Type, span: &Span, bindings: &mut HashMap<String, Type>) {
+        match pattern {
+            Pattern::Binding(name) => {
+                bindings.insert(name.clone(), subject.clone());
+            }
+            Pattern::Variant { enumName, variant, payload } => {
+                let types = match self.variantTypes(enumName, variant, payload.len()) {
+                    Ok(types) => types,
+                    Err(error) => {
+                        self.errors.push(error.at(span.clone()));
+                        Vec::new()
+                    }
+                };
+                for (index, inner) in payload.iter().enumerate() {
+                    let innerType = types.get(index).and_then(|t| self.declaredType(t).ok()).unwrap_or(Type::Any);
+                    self.bindPattern(inner, &innerType, span, bindings);
+                }
+            }
+            Pattern::Wildcard | Pattern::Literal(_) | Pattern::Range { .. } => {}
+        }
+    }
+
+    // The payload types of `enumName::variant`, which has to take `count` values
+    fn variantTypes(&self, enumName: &str, variant: &str, count: usize) -> Result<Vec<String>, JadeError> {
+        let variants = self.enums.get(enumName).ok_or_else(|| self.unknownType(enumNotFoundError(enumName), enumName))?;
+        let (_, types) = variants.iter().find(|(v, _)| v == variant).ok_or_else(|| {
+            suggest(variantNotFoundError(enumName, variant), variant, variants.iter().map(|(v, _)| v))
+        })?;
+        if types.len() != count {
+            return Err(payloadCountError(enumName, variant, types.len(), count));
+        }
+        Ok(types.clone())
+    }
+
+    // The type of a variable, `match` bindings first
+    fn variable(&self, name: &str) -> Result<Type, JadeError> {
+        if let Some(bound) = self.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+            return Ok(bound.clone());
+        }
+        self.variables.get(name).cloned().ok_or_else(|| {
+            let names: Vec<&String> = self.scopes.iter().flat_map(|s| s.keys()).chain(self.variables.keys()).collect();
+            suggest(variableNotFoundError(name), name, names.into_iter())
+        })
+    }
+
+    // Walks struct fields from a value of type `start`
+    fn fieldPathType(&self, start: Type, path: &[String]) -> Result<Type, JadeError> {
+        let mut current = start;
+        for field in path {
+            current = match current.nonNull() {
+                Type::Any => return Ok(Type::Any),
+                Type::Named(name) if self.structs.contains_key(name) => {
+                    let fields = &self.structs[name];
+                    let (_, fieldType) = fields
+                        .iter()
+                        .find(|(f, _)| f == field)
+                        .ok_or_else(|| suggest(fieldNotFoundError(name, field), field, fields.iter().map(|(f, _)| f)))?;
+                    self.declaredType(fieldType)?
+                }
+                other => return Err(fieldAccessError(field, &other.to_string())),
+            };
+        }
+        Ok(current)
+    }
+
+    // The type a type name in the source stands for
+    fn declaredType(&self, name: &str) -> Result<Type, JadeError> {
+        if let Some(inner) = name.strip_suffix('?') {
+            return Ok(Type::Nullable(Box::new(self.declaredType(inner)?)));
+        }
+        Ok(match name {
+            "$" | "@" => Type::Any,
+            "int" | "int64" => Type::Int(None),
+            "int8" | "uint8" | "int16" | "uint16" | "int32" | "uint32" | "uint64" => Type::Int(Some(name.into())),
+            "float" | "float64" => Type::Float(None),
+            "float32" => Type::Float(Some(name.into())),
+            "bool" => Type::Bool,
+            "string" | "sink" => Type::String,
+            _ if self.structs.contains_key(name) || self.enums.contains_key(name) => Type::Named(name.into()),
+            _ => return Err(self.unknownType(typeNotFoundError(name), name)),
+        })
+    }
+
+    // A "did you mean" for type names, from the structs and enums so far
+    fn unknownType(&self, error: JadeError, name: &str) -> JadeError {
+        suggest(error, name, self.structs.keys().chain(self.enums.keys()))
+    }
+}
+
+/// The type a binary operator gives for these operand types, following
+/// what evaluate_binary_op supports.
+fn binaryType(op: &BinaryOperator, left: &Type, right: &Type) -> Result<Type, JadeError> {
+    use BinaryOperator::*;
+
+    // Null only makes sense in equality checks
+    if matches!(left, Type::Null) || matches!(right, Type::Null) {
+        return match op {
+            Equal | NotEqual => Ok(Type::Bool),
+            _ => Err(nullOperandError(op)),
+        };
+    }
+
+    let (l, r) = (left.nonNull(), right.nonNull());
+    let mismatch = || operandTypesError(op, &left.to_string(), &right.to_string());
+    match op {
+        Equal | NotEqual | TrueEquality => Ok(Type::Bool),
+
+        // Numbers of any width compare, and so do strings and bools
+        GreaterThan | LessThan | GreaterOrEqual | LessOrEqual => match (l, r) {
+            (Type::Any, _) | (_, Type::Any) => Ok(Type::Bool),
+            (Type::String, Type::String) | (Type::Bool, Type::Bool) => Ok(Type::Bool),
+            (l, r) if l.isNumber() && r.isNumber() => Ok(Type::Bool),
+            _ => Err(mismatch()),
+        },
+
+        // Math keeps the left side's type
+        Add | Subtract | Multiply | Divide => match (l, r) {
+            (Type::Any, _) => Ok(Type::Any),
+            (l, Type::Any) if l.isNumber() => Ok(l.clone()),
+            (l, r) if l.isNumber() && r.isNumber() => Ok(l.clone()),
+            _ => Err(mismatch()),
+        },
+
+        _ => Err(unsupportedOperatorError(op)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lexCode;
+    use crate::parser::parseCode;
+
+    fn check(code: &str) -> Vec<String> {
+        let parsed = parseCode(lexCode(code.to_string(), "test.bg").unwrap()).unwrap();
+        match checkTypes(&parsed) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.iter().map(|e| e.diagnostic().message.clone()).collect(),
+        }
+    }
+
+    #[test]
+    fn acceptsWellTypedCode() {
+        let code = "
+            struct Point { x int; y int?; }
+            enum Shape { Circle(float), Empty }
+            var p Point = Point { x = 1 };
+            var s Shape = Shape::Circle(2.5);
+            var area float = match (s) { Shape::Circle(r) => r * r, _ => 0.0 };
+            var n int8 = 5;
+            var total int = n + p.x + (p.y ?? 0);
+            if (total > 3) { total = 0; }
+        ";
+        assert!(check(code).is_empty());
+    }
+
+    #[test]
+    fn rejectsMismatchedDeclarationsAndAssignments() {
+        let errors = check("var a int = \"one\";\nvar b bool = true;\nb = 2;\nvar c float = 1;");
+        assert_eq!(
+            errors,
+            vec![
+                "Type mismatch! Expected: int, Found: string",
+                "Type mismatch! Expected: bool, Found: int",
+                "Type mismatch! Expected: float, Found: int",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejectsBadOperands() {
+        let errors = check("var a int = 1 + \"two\";\nvar b bool = \"x\" < 2;\nvar c int = null + 1;");
+        assert_eq!(
+            errors,
+            vec![
+                "Add not supported between given types: int and string",
+                "LessThan not supported between given types: string and int",
+                "Can't use null with Add",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejectsOperatorsTheOperandsDoNotHave() {
+        assert_eq!(
+            check("var a bool = true * false;\nvar b string = \"a\" - \"b\";"),
+            vec![
+                "Multiply not supported between given types: bool and bool",
+                "Subtract not supported between given types: string and string",
+            ]
+        );
+    }
+
+    #[test]
+    fn reportsUndeclaredVariablesWithSpans() {
+        let parsed = parseCode(lexCode("var total int = 1;\ntotl = 2;\nvar x int = y;".to_string(), "test.bg").unwrap()).unwrap();
+        let errors = checkTypes(&parsed).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].diagnostic().span.as_ref().unwrap().line, 2);
+        assert_eq!(errors[0].diagnostic().hint.as_deref(), Some("did you mean `total`?"));
+        assert_eq!(errors[1].diagnostic().span.as_ref().unwrap().line, 3);
+    }
+
+    #[test]
+    fn infersTypesFromTheValue() {
+        assert!(check("var a = 5;\nvar b = 2.5;\nvar c = \"hi\";\na = 300;\nb = b * 2;\nc = \"bye\";").is_empty());
+        assert_eq!(
+            check("var a = 5;\na = \"five\";\nvar b = 1.5;\nb = true;\nvar c = null;"),
+            vec![
+                "Type mismatch! Expected: int, Found: string",
+                "Type mismatch! Expected: float, Found: bool",
+                "Can't tell the type of 'c' from null",
+            ]
+        );
+    }
+
+    #[test]
+    fn widthsHaveToMatch() {
+        assert!(check("var a int16 = 5;\nvar b int16 = a;\nvar c int = a;").is_empty());
+        assert_eq!(check("var a int16 = 5;\nvar b int8 = a;"), vec!["Type mismatch! Expected: int8, Found: int16"]);
+    }
+
+    #[test]
+    fn patternsNeedRealVariants() {
+        let code = "enum C { A(int), B }
+var c = C::B;
+var a int = match (c) { C::A(x) => x, C::B => 2, C::Bb => 3 };
+var b int = match (c) { C::Z => 1, _ => 2 };
+var d int = match (c) { C::A => 1, C::B => 2 };
+var e int = match (c) { C::A(x, y) => 1, _ => 2 };";
+        assert_eq!(
+            check(code),
+            vec![
+                "Enum 'C' has no variant 'Bb'",
+                "Enum 'C' has no variant 'Z'",
+                "C::A takes 1 value(s), found 0",
+                "C::A takes 1 value(s), found 2",
+            ]
+        );
+        let parsed = parseCode(lexCode(code.to_string(), "test.bg").unwrap()).unwrap();
+        let errors = checkTypes(&parsed).unwrap_err();
+        assert_eq!(errors[0].diagnostic().hint.as_deref(), Some("did you mean `B`?"));
+        assert_eq!(errors[0].diagnostic().span.as_ref().unwrap().line, 3);
+    }
+}
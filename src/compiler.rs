@@ -0,0 +1,470 @@
+use std::collections::HashMap;
+use crate::lexer::Span;
+use crate::parser::{BinaryOperator, Expression, ExpressionKind, Pattern, Statement};
+use crate::runtime::Value;
+
+/// One instruction for the VM. Anything bigger than a number lives in
+/// a table on the Chunk and the instruction holds its index, jumps hold
+/// the index of the instruction they go to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    /// Push `constants[i]`
+    Constant(usize),
+    /// Push the value of the variable in slot `i`
+    Load(usize),
+    /// Pop a value into slot `i` as a new variable of type `types[t]`,
+    /// already fitted by a `Coerce`. Without a type it's inferred.
+    Declare(usize, Option<usize>),
+    /// Fail unless the variable in slot `i` has been declared
+    Check(usize),
+    /// Pop a value into the variable in slot `i`, fitting it to its type
+    Store(usize),
+    /// Fail unless the variable in slot `i` has the fields `paths[p]`
+    CheckField(usize, usize),
+    /// Pop a value into the field `paths[p]` of the variable in slot `i`
+    StoreField(usize, usize),
+    /// Fit the value on top to `types[t]`
+    Coerce(usize),
+    /// Pop two values and push what the operator gives for them
+    Binary(BinaryOperator),
+    /// Replace the struct on top with its field `names[n]`, `true` for `?.`
+    Field(usize, bool),
+    /// Pop the fields of `structLiterals[s]` and push the struct
+    Struct(usize),
+    /// Pop the payload of `variants[v]` and push the enum value
+    Variant(usize),
+    /// Test the subject on top against `arms[a]`. When it matches it is
+    /// popped and the names are bound, otherwise jump to `target`.
+    MatchArm(usize, usize),
+    /// Pop the subject no arm matched and fail
+    NoMatch,
+    /// Jump to `target`
+    Jump(usize),
+    /// Pop a bool and jump to `target` if it's false
+    JumpIfFalse(usize),
+    /// Pop a bool and jump to `target` if it's true
+    JumpIfTrue(usize),
+    /// Jump to `target` keeping the value on top unless it's null, then
+    /// pop it and carry on. This is `??`.
+    JumpIfNotNull(usize),
+    /// Pop the file name if there's one, then `n` values, and print
+    /// them on one line or write them to the file
+    Print(usize, bool),
+    /// Pop a value and append it to the variable in slot `i`
+    Append(usize),
+    /// Pop a file name, then a value, and append the value to the file
+    AppendFile,
+    /// Read the file named on top if there's one, otherwise a line of
+    /// input, into the variable in slot `i`
+    Read(usize, bool),
+    /// Pop `n` arguments, call the function `names[f]` with them and
+    /// push what it gives back
+    Call(usize, usize),
+    /// Drop the value on top
+    Pop,
+    /// Declare `structs[s]`
+    DeclareStruct(usize),
+    /// Declare `enums[e]`
+    DeclareEnum(usize),
+}
+
+// Variant names and their payload types, as declared in an enum
+type Variants = Vec<(String, Vec<String>)>;
+
+/// A struct literal: the struct, the fields in the order their values
+/// are pushed, and whether those values were already fitted to the
+/// field types.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructLiteral {
+    pub name: String,
+    pub fields: Vec<String>,
+    pub coerced: bool,
+}
+
+/// A compiled program, its instructions, the span each one came from
+/// (errors point there) and the tables the instructions index into.
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<Op>,
+    pub spans: Vec<Span>,
+    pub constants: Vec<Value>,
+    pub slots: Vec<String>, // The name of the variable in each slot
+    pub globals: HashMap<String, usize>,
+    pub types: Vec<String>,
+    pub names: Vec<String>,
+    pub paths: Vec<Vec<String>>,
+    pub structLiterals: Vec<StructLiteral>,
+    pub variants: Vec<(String, String, usize)>, // Enum, variant and payload count
+    pub arms: Vec<(Pattern, Vec<usize>)>,       // Pattern and the slots of the names it binds
+    pub structs: Vec<(String, Vec<(String, String)>)>,
+    pub enums: Vec<(String, Variants)>,
+}
+
+/// Compiles a parsed program to bytecode for the VM. Nothing is checked
+/// here, anything that can go wrong is reported when it runs so both
+/// engines fail the same way at the same point.
+pub fn compile(code: &[Statement]) -> Chunk {
+    let mut compiler = Compiler::default();
+    collectTypes(code, &mut compiler);
+    for statement in code {
+        compiler.statement(statement);
+    }
+    compiler.chunk
+}
+
+#[derive(Default)]
+struct Compiler {
+    chunk: Chunk,
+    scopes: Vec<HashMap<String, usize>>, // Names bound by `match` arms
+    // The fields and payloads of every struct and enum, so values can be
+    // fitted where they're written. `None` when a name is declared twice
+    // differently, then only the VM can tell.
+    structs: HashMap<String, Option<Vec<(String, String)>>>,
+    enums: HashMap<String, Option<Variants>>,
+}
+
+// Every struct and enum declared anywhere in the program
+fn collectTypes<'a>(code: impl IntoIterator<Item = &'a Statement>, compiler: &mut Compiler) {
+    for statement in code {
+        match statement {
+            Statement::StructDec { name, fields, .. } => {
+                let known = compiler.structs.entry(name.clone()).or_insert_with(|| Some(fields.clone()));
+                if known.as_ref() != Some(fields) {
+                    *known = None;
+                }
+            }
+            Statement::EnumDec { name, variants, .. } => {
+                let known = compiler.enums.entry(name.clone()).or_insert_with(|| Some(variants.clone()));
+                if known.as_ref() != Some(variants) {
+                    *known = None;
+                }
+            }
+            _ => {}
+        }
+        collectTypes(statement.children(), compiler);
+    }
+}
+
+// The index of `text` in `table`, adding it if it isn't there yet
+fn intern(table: &mut Vec<String>, text: &str) -> usize {
+    match table.iter().position(|t| t == text) {
+        Some(index) => index,
+        None => {
+            table.push(text.to_string());
+            table.len() - 1
+        }
+    }
+}
+
+impl Compiler {
+    fn emit(&mut self, op: Op, span: &Span) -> usize {
+        self.chunk.code.push(op);
+        self.chunk.spans.push(span.clone());
+        self.chunk.code.len() - 1
+    }
+
+    // Points the jump at `at` to the next instruction
+    fn patch(&mut self, at: usize) {
+        let here = self.chunk.code.len();
+        match &mut self.chunk.code[at] {
+            Op::Jump(target) |
+            Op::JumpIfFalse(target) |
+            Op::JumpIfTrue(target) |
+            Op::JumpIfNotNull(target) |
+            Op::MatchArm(_, target) => *target = here,
+            other => unreachable!("{:?} isn't a jump", other),
+        }
+    }
+
+    fn newSlot(&mut self, name: &str) -> usize {
+        self.chunk.slots.push(name.to_string());
+        self.chunk.slots.len() - 1
+    }
+
+    // The slot of a global, every use of a name shares one like the
+    // runtime's single table of variables
+    fn global(&mut self, name: &str) -> usize {
+        match self.chunk.globals.get(name) {
+            Some(slot) => *slot,
+            None => {
+                let slot = self.newSlot(name);
+                self.chunk.globals.insert(name.to_string(), slot);
+                slot
+            }
+        }
+    }
+
+    // The slot a name in an expression refers to, `match` bindings first
+    fn variable(&mut self, name: &str) -> usize {
+        match self.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+            Some(slot) => *slot,
+            None => self.global(name),
+        }
+    }
+
+    fn constant(&mut self, value: Value, span: &Span) {
+        self.chunk.constants.push(value);
+        let index = self.chunk.constants.len() - 1;
+        self.emit(Op::Constant(index), span);
+    }
+
+    fn coerce(&mut self, datatype: &str, span: &Span) -> usize {
+        let index = intern(&mut self.chunk.types, datatype);
+        self.emit(Op::Coerce(index), span);
+        index
+    }
+
+    fn block(&mut self, body: &[Statement]) {
+        for statement in body {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::VarDec { name, datatype, value, span, .. } => {
+                self.expression(value);
+                let datatype = datatype.as_ref().map(|datatype| self.coerce(datatype, &value.span));
+                let slot = self.global(name);
+                self.emit(Op::Declare(slot, datatype), span);
+            }
+
+            Statement::StructDec { name, fields, span, .. } => {
+                self.chunk.structs.push((name.clone(), fields.clone()));
+                self.emit(Op::DeclareStruct(self.chunk.structs.len() - 1), span);
+            }
+
+            Statement::EnumDec { name, variants, span, .. } => {
+                self.chunk.enums.push((name.clone(), variants.clone()));
+                self.emit(Op::DeclareEnum(self.chunk.enums.len() - 1), span);
+            }
+
+            // The variable is looked up before the value is worked out,
+            // like the runtime does
+            Statement::VarSet { name, value, span, .. } => {
+                let slot = self.global(name);
+                self.emit(Op::Check(slot), span);
+                self.expression(value);
+                self.emit(Op::Store(slot), &value.span);
+            }
+
+            Statement::FieldSet { name, path, value, span, .. } => {
+                let slot = self.global(name);
+                self.chunk.paths.push(path.clone());
+                let path = self.chunk.paths.len() - 1;
+                self.emit(Op::CheckField(slot, path), span);
+                self.expression(value);
+                self.emit(Op::StoreField(slot, path), &value.span);
+            }
+
+            Statement::Print { values, target, span } => {
+                for value in values.iter().chain(target) {
+                    self.expression(value);
+                }
+                self.emit(Op::Print(values.len(), target.is_some()), span);
+            }
+
+            Statement::Append { value, target, span } => {
+                self.expression(value);
+                match &target.kind {
+                    ExpressionKind::Variable { name, .. } => {
+                        let slot = self.global(name);
+                        self.emit(Op::Append(slot), span);
+                    }
+                    _ => {
+                        self.expression(target);
+                        self.emit(Op::AppendFile, span);
+                    }
+                }
+            }
+
+            Statement::Read { name, source, span, .. } => {
+                let slot = self.global(name);
+                if let Some(source) = source {
+                    self.expression(source);
+                }
+                self.emit(Op::Read(slot, source.is_some()), span);
+            }
+
+            Statement::Expression { value, span } => {
+                self.expression(value);
+                self.emit(Op::Pop, span);
+            }
+
+            Statement::If { condition, body, otherwise, span } => {
+                self.expression(condition);
+                let skip = self.emit(Op::JumpIfFalse(0), &condition.span);
+                self.block(body);
+                match otherwise {
+                    Some(otherwise) => {
+                        let end = self.emit(Op::Jump(0), span);
+                        self.patch(skip);
+                        self.block(otherwise);
+                        self.patch(end);
+                    }
+                    None => self.patch(skip),
+                }
+            }
+
+            Statement::Unless { condition, body, .. } => {
+                self.expression(condition);
+                let skip = self.emit(Op::JumpIfTrue(0), &condition.span);
+                self.block(body);
+                self.patch(skip);
+            }
+
+            Statement::While { condition, body, span } | Statement::Until { condition, body, span } => {
+                let start = self.chunk.code.len();
+                self.expression(condition);
+                let exit = match statement {
+                    Statement::While { .. } => self.emit(Op::JumpIfFalse(0), &condition.span),
+                    _ => self.emit(Op::JumpIfTrue(0), &condition.span),
+                };
+                self.block(body);
+                self.emit(Op::Jump(start), span);
+                self.patch(exit);
+            }
+
+            Statement::For { init, condition, step, body, span } => {
+                if let Some(init) = init {
+                    self.statement(init);
+                }
+                let start = self.chunk.code.len();
+                let exit = condition.as_ref().map(|condition| {
+                    self.expression(condition);
+                    self.emit(Op::JumpIfFalse(0), &condition.span)
+                });
+                self.block(body);
+                if let Some(step) = step {
+                    self.statement(step);
+                }
+                self.emit(Op::Jump(start), span);
+                if let Some(exit) = exit {
+                    self.patch(exit);
+                }
+            }
+        }
+    }
+
+    // Leaves the value of the expression on the stack
+    fn expression(&mut self, expr: &Expression) {
+        let span = &expr.span;
+        match &expr.kind {
+            ExpressionKind::Number(value) => self.constant(value.clone(), span),
+            ExpressionKind::String(text) => self.constant(Value::String(text.clone()), span),
+            ExpressionKind::Boolean(b) => self.constant(Value::Bool(*b), span),
+            ExpressionKind::Null => self.constant(Value::Null, span),
+
+            ExpressionKind::Variable { name, .. } => {
+                let slot = self.variable(name);
+                self.emit(Op::Load(slot), span);
+            }
+
+            // Fields are worked out in declared order like the runtime
+            // does, when the struct is known here
+            ExpressionKind::StructLiteral { name, fields } => {
+                let declared = self
+                    .structs
+                    .get(name)
+                    .cloned()
+                    .flatten()
+                    .filter(|declared| fields.iter().all(|(f, _)| declared.iter().any(|(d, _)| d == f)));
+                let order: Vec<(&Expression, &String, Option<String>)> = match &declared {
+                    Some(declared) => declared
+                        .iter()
+                        .filter_map(|(field, fieldType)| {
+                            fields.iter().find(|(f, _)| f == field).map(|(f, value)| (value, f, Some(fieldType.clone())))
+                        })
+                        .collect(),
+                    None => fields.iter().map(|(f, value)| (value, f, None)).collect(),
+                };
+
+                let mut given: Vec<String> = Vec::new();
+                for (value, field, fieldType) in order {
+                    self.expression(value);
+                    if let Some(fieldType) = fieldType {
+                        self.coerce(&fieldType, &value.span);
+                    }
+                    given.push(field.clone());
+                }
+                self.chunk.structLiterals.push(StructLiteral { name: name.clone(), fields: given, coerced: declared.is_some() });
+                self.emit(Op::Struct(self.chunk.structLiterals.len() - 1), span);
+            }
+
+            ExpressionKind::FieldAccess { object, field, optional } => {
+                self.expression(object);
+                let field = intern(&mut self.chunk.names, field);
+                self.emit(Op::Field(field, *optional), span);
+            }
+
+            ExpressionKind::EnumVariant { enumName, variant, payload } => {
+                let types = self
+                    .enums
+                    .get(enumName)
+                    .cloned()
+                    .flatten()
+                    .and_then(|variants| variants.into_iter().find(|(v, _)| v == variant))
+                    .map(|(_, types)| types)
+                    .filter(|types| types.len() == payload.len());
+                for (index, value) in payload.iter().enumerate() {
+                    self.expression(value);
+                    if let Some(types) = &types {
+                        self.coerce(&types[index], &value.span);
+                    }
+                }
+                self.chunk.variants.push((enumName.clone(), variant.clone(), payload.len()));
+                self.emit(Op::Variant(self.chunk.variants.len() - 1), span);
+            }
+
+            ExpressionKind::Match { subject, arms } => {
+                self.expression(subject);
+                let mut ends: Vec<usize> = Vec::new();
+                for (pattern, arm) in arms {
+                    let mut scope: HashMap<String, usize> = HashMap::new();
+                    let slots: Vec<usize> = pattern
+                        .bindings()
+                        .into_iter()
+                        .map(|name| {
+                            let slot = self.newSlot(name);
+                            scope.insert(name.clone(), slot);
+                            slot
+                        })
+                        .collect();
+
+                    self.chunk.arms.push((pattern.clone(), slots));
+                    let next = self.emit(Op::MatchArm(self.chunk.arms.len() - 1, 0), span);
+                    self.scopes.push(scope);
+                    self.expression(arm);
+                    self.scopes.pop();
+                    ends.push(self.emit(Op::Jump(0), span));
+                    self.patch(next);
+                }
+                self.emit(Op::NoMatch, span);
+                for end in ends {
+                    self.patch(end);
+                }
+            }
+
+            ExpressionKind::Call { name, args } => {
+                for arg in args {
+                    self.expression(arg);
+                }
+                let name = intern(&mut self.chunk.names, name);
+                self.emit(Op::Call(name, args.len()), span);
+            }
+
+            ExpressionKind::BinaryOp { op: BinaryOperator::NullCoalesce, left, right } => {
+                self.expression(left);
+                let skip = self.emit(Op::JumpIfNotNull(0), span);
+                self.expression(right);
+                self.patch(skip);
+            }
+
+            ExpressionKind::BinaryOp { op, left, right } => {
+                self.expression(left);
+                self.expression(right);
+                self.emit(Op::Binary(op.clone()), span);
+            }
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+
+use crate::jadeErrors::{literalRangeError, typeMismatchError, JadeError};
+use crate::runtime::Value;
+
+impl Value {
+    /// The Jade type of the value, as errors name it.
+    pub fn type_name(&self) -> String {
+        let name = match self {
+            Value::Int8(_) => "int8",
+            Value::UInt8(_) => "uint8",
+            Value::Int16(_) => "int16",
+            Value::UInt16(_) => "uint16",
+            Value::Int32(_) => "int32",
+            Value::UInt32(_) => "uint32",
+            Value::Int64(_) => "int64",
+            Value::UInt64(_) => "uint64",
+            Value::Float(_) => "float",
+            Value::Float32(_) => "float32",
+            Value::Float64(_) => "float64",
+            Value::Bool(_) => "bool",
+            Value::String(_) => "string",
+            Value::Array(_) | Value::FixedArray(..) => "array",
+            Value::Slice(_) => "slice",
+            Value::Pointer(_) => "pointer",
+            Value::Map(_) => "map",
+            Value::Struct(name, _) | Value::Enum(name, _, _) => name,
+            Value::Null => "null",
+        };
+        name.to_string()
+    }
+
+    /// Any integer that fits in an i64, whatever its width.
+    pub fn as_i64(&self) -> Option<i64> {
+        wideInteger(self).and_then(|v| i64::try_from(v).ok())
+    }
+
+    /// Any integer that fits in a u64, whatever its width.
+    pub fn as_u64(&self) -> Option<u64> {
+        wideInteger(self).and_then(|v| u64::try_from(v).ok())
+    }
+
+    /// Any number, integers are widened.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Float(v) | Value::Float64(v) => Some(*v),
+            Value::Float32(v) => Some(*v as f64),
+            other => wideInteger(other).map(|v| v as f64),
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(text) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+// Every integer width fits in an i128
+fn wideInteger(value: &Value) -> Option<i128> {
+    match value {
+        Value::Int8(v) => Some(*v as i128),
+        Value::UInt8(v) => Some(*v as i128),
+        Value::Int16(v) => Some(*v as i128),
+        Value::UInt16(v) => Some(*v as i128),
+        Value::Int32(v) => Some(*v as i128),
+        Value::UInt32(v) => Some(*v as i128),
+        Value::Int64(v) => Some(*v as i128),
+        Value::UInt64(v) => Some(*v as i128),
+        _ => None,
+    }
+}
+
+// An integer of any width, as long as its value fits in `T`
+fn integer<T: TryFrom<i128>>(value: &Value, expected: &str) -> Result<T, JadeError> {
+    let wide = wideInteger(value).ok_or_else(|| mismatch(expected, value))?;
+    T::try_from(wide).map_err(|_| literalRangeError(&wide.to_string(), expected))
+}
+
+fn mismatch(expected: &str, found: &Value) -> JadeError {
+    typeMismatchError(expected, &found.type_name())
+}
+
+macro_rules! integerConversions {
+    ($($rust:ty => $variant:ident, $jade:literal;)*) => {$(
+        impl From<$rust> for Value {
+            fn from(value: $rust) -> Self {
+                Value::$variant(value)
+            }
+        }
+
+        impl TryFrom<Value> for $rust {
+            type Error = JadeError;
+
+            fn try_from(value: Value) -> Result<Self, JadeError> {
+                integer(&value, $jade)
+            }
+        }
+    )*};
+}
+
+integerConversions! {
+    i8 => Int8, "int8";
+    u8 => UInt8, "uint8";
+    i16 => Int16, "int16";
+    u16 => UInt16, "uint16";
+    i32 => Int32, "int32";
+    u32 => UInt32, "uint32";
+    i64 => Int64, "int64";
+    u64 => UInt64, "uint64";
+}
+
+impl From<f32> for Value {
+    fn from(value: f32) -> Self {
+        Value::Float32(value)
+    }
+}
+
+impl TryFrom<Value> for f32 {
+    type Error = JadeError;
+
+    fn try_from(value: Value) -> Result<Self, JadeError> {
+        value.as_f64().map(|v| v as f32).ok_or_else(|| mismatch("float32", &value))
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Float64(value)
+    }
+}
+
+impl TryFrom<Value> for f64 {
+    type Error = JadeError;
+
+    fn try_from(value: Value) -> Result<Self, JadeError> {
+        value.as_f64().ok_or_else(|| mismatch("float64", &value))
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = JadeError;
+
+    fn try_from(value: Value) -> Result<Self, JadeError> {
+        value.as_bool().ok_or_else(|| mismatch("bool", &value))
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = JadeError;
+
+    fn try_from(value: Value) -> Result<Self, JadeError> {
+        match value {
+            Value::String(text) => Ok(text),
+            other => Err(mismatch("string", &other)),
+        }
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(values: Vec<T>) -> Self {
+        Value::Array(values.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: TryFrom<Value, Error = JadeError>> TryFrom<Value> for Vec<T> {
+    type Error = JadeError;
+
+    fn try_from(value: Value) -> Result<Self, JadeError> {
+        let values = match value {
+            Value::Array(values) | Value::FixedArray(values, _) => values,
+            Value::Slice(values) => values.into_vec(),
+            other => return Err(mismatch("array", &other)),
+        };
+        values.into_iter().map(T::try_from).collect()
+    }
+}
+
+impl<T: Into<Value>> From<HashMap<String, T>> for Value {
+    fn from(entries: HashMap<String, T>) -> Self {
+        Value::Map(entries.into_iter().map(|(key, value)| (key, value.into())).collect())
+    }
+}
+
+// Structs convert too, by field name
+impl<T: TryFrom<Value, Error = JadeError>> TryFrom<Value> for HashMap<String, T> {
+    type Error = JadeError;
+
+    fn try_from(value: Value) -> Result<Self, JadeError> {
+        let entries: Vec<(String, Value)> = match value {
+            Value::Map(entries) => entries.into_iter().collect(),
+            Value::Struct(_, fields) => fields,
+            other => return Err(mismatch("map", &other)),
+        };
+        entries.into_iter().map(|(key, value)| Ok((key, T::try_from(value)?))).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbersRoundTrip() {
+        assert_eq!(Value::from(-5i8), Value::Int8(-5));
+        assert_eq!(Value::from(70_000u32), Value::UInt32(70_000));
+        assert_eq!(Value::from(1.5f32), Value::Float32(1.5));
+        assert_eq!(i8::try_from(Value::from(-5i8)), Ok(-5));
+        assert_eq!(u64::try_from(Value::from(u64::MAX)), Ok(u64::MAX));
+        assert_eq!(f64::try_from(Value::from(0.25f64)), Ok(0.25));
+    }
+
+    #[test]
+    fn integersConvertAcrossWidths() {
+        assert_eq!(u8::try_from(Value::Int64(200)), Ok(200));
+        assert_eq!(i64::try_from(Value::UInt16(7)), Ok(7));
+        assert_eq!(f32::try_from(Value::Int8(-3)), Ok(-3.0));
+        for value in [Value::Int8(-9), Value::UInt8(9), Value::Int16(-9), Value::UInt32(9), Value::UInt64(9)] {
+            assert_eq!(value.as_i64().map(i64::abs), Some(9), "{:?}", value);
+            assert_eq!(value.as_f64().map(f64::abs), Some(9.0), "{:?}", value);
+        }
+        assert_eq!(Value::UInt64(u64::MAX).as_i64(), None);
+        assert_eq!(Value::Int8(-1).as_u64(), None);
+        assert_eq!(Value::Float64(1.0).as_i64(), None);
+    }
+
+    #[test]
+    fn errorsNameBothTypes() {
+        let error = i32::try_from(Value::from("7")).unwrap_err();
+        assert_eq!(error.diagnostic().message, "Type mismatch! Expected: int32, Found: string");
+        let error = u8::try_from(Value::Int16(-1)).unwrap_err();
+        assert_eq!(error.diagnostic().message, "`-1` doesn't fit in uint8");
+        let error = bool::try_from(Value::Struct("Point".into(), Vec::new())).unwrap_err();
+        assert_eq!(error.diagnostic().message, "Type mismatch! Expected: bool, Found: Point");
+    }
+
+    #[test]
+    fn collectionsConvertEachValue() {
+        let value = Value::from(vec![1i64, 2, 3]);
+        assert_eq!(value, Value::Array(vec![Value::Int64(1), Value::Int64(2), Value::Int64(3)]));
+        assert_eq!(Vec::<u8>::try_from(value), Ok(vec![1, 2, 3]));
+        assert!(Vec::<bool>::try_from(Value::from(vec![true])).is_ok());
+        assert!(Vec::<bool>::try_from(Value::from(vec![1i8])).is_err());
+
+        let scores = HashMap::from([("ann".to_string(), 3u16), ("bo".to_string(), 5)]);
+        assert_eq!(HashMap::<String, u16>::try_from(Value::from(scores.clone())), Ok(scores));
+
+        let point = Value::Struct("Point".into(), vec![("x".into(), Value::Int8(1)), ("y".into(), Value::Int8(2))]);
+        let fields = HashMap::<String, i64>::try_from(point).unwrap();
+        assert_eq!(fields.get("y"), Some(&2));
+    }
+}
@@ -0,0 +1,434 @@
+use crate::lexer::{Span, Token};
+use crate::parser::{Expression, ExpressionKind, Pattern, Statement};
+use crate::runtime::Value;
+
+/// A JSON value. Objects keep their keys in the order they were added
+/// so the output is stable from run to run.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn object(fields: Vec<(&str, Json)>) -> Json {
+        Json::Object(fields.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+    }
+
+    fn string(text: &str) -> Json {
+        Json::String(text.to_string())
+    }
+
+    fn optional(text: &Option<String>) -> Json {
+        text.as_deref().map_or(Json::Null, Json::string)
+    }
+
+    /// Pretty-printed JSON with two space indents.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out, 0);
+        out
+    }
+
+    fn write(&self, out: &mut String, indent: usize) {
+        let pad = "  ".repeat(indent + 1);
+        match self {
+            Json::Null => out.push_str("null"),
+            Json::Bool(b) => out.push_str(&b.to_string()),
+            Json::Number(n) => out.push_str(n),
+            Json::String(s) => out.push_str(&quote(s)),
+            Json::Array(items) if items.is_empty() => out.push_str("[]"),
+            Json::Array(items) => {
+                out.push_str("[\n");
+                for (idx, item) in items.iter().enumerate() {
+                    out.push_str(&pad);
+                    item.write(out, indent + 1);
+                    out.push_str(if idx + 1 < items.len() { ",\n" } else { "\n" });
+                }
+                out.push_str(&"  ".repeat(indent));
+                out.push(']');
+            }
+            Json::Object(fields) if fields.is_empty() => out.push_str("{}"),
+            Json::Object(fields) => {
+                out.push_str("{\n");
+                for (idx, (key, value)) in fields.iter().enumerate() {
+                    out.push_str(&format!("{}{}: ", pad, quote(key)));
+                    value.write(out, indent + 1);
+                    out.push_str(if idx + 1 < fields.len() { ",\n" } else { "\n" });
+                }
+                out.push_str(&"  ".repeat(indent));
+                out.push('}');
+            }
+        }
+    }
+
+    /// A readable tree: each node is its `type` and plain fields on one
+    /// line, with nested nodes indented under it. Spans show as
+    /// `@line:column`.
+    pub fn tree(&self) -> String {
+        let mut out = String::new();
+        self.writeTree(&mut out, 0, None);
+        out
+    }
+
+    fn writeTree(&self, out: &mut String, indent: usize, label: Option<&str>) {
+        let pad = "  ".repeat(indent);
+        let label = label.map(|l| format!("{}: ", l)).unwrap_or_default();
+        match self {
+            Json::Array(items) => {
+                for item in items {
+                    item.writeTree(out, indent, None);
+                }
+            }
+            Json::Object(fields) => {
+                let mut parts: Vec<String> = Vec::new();
+                let mut nested: Vec<(&str, &Json)> = Vec::new();
+                for (key, value) in fields {
+                    match (key.as_str(), value) {
+                        ("type", Json::String(kind)) => parts.push(kind.clone()),
+                        ("span", span) => parts.push(format!("@{}", spanPosition(span))),
+                        (_, Json::Object(_)) => nested.push((key, value)),
+                        (_, Json::Array(items)) if !items.is_empty() => nested.push((key, value)),
+                        (_, Json::Array(_) | Json::Null) => {}
+                        (_, scalar) => parts.push(format!("{}={}", key, scalar.render())),
+                    }
+                }
+                out.push_str(&format!("{}{}{}\n", pad, label, parts.join(" ")));
+                for (key, value) in nested {
+                    match value {
+                        Json::Array(items) => {
+                            out.push_str(&format!("{}  {}:\n", pad, key));
+                            for item in items {
+                                item.writeTree(out, indent + 2, None);
+                            }
+                        }
+                        _ => value.writeTree(out, indent + 1, Some(key)),
+                    }
+                }
+            }
+            scalar => out.push_str(&format!("{}{}{}\n", pad, label, scalar.render())),
+        }
+    }
+}
+
+fn quote(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// `line:column` out of a span object
+fn spanPosition(span: &Json) -> String {
+    let field = |name: &str| match span {
+        Json::Object(fields) => fields.iter().find(|(k, _)| k == name).map(|(_, v)| v.render()),
+        _ => None,
+    };
+    format!("{}:{}", field("line").unwrap_or_default(), field("column").unwrap_or_default())
+}
+
+pub fn spanJson(span: &Span) -> Json {
+    Json::object(vec![
+        ("file", Json::string(&span.file)),
+        ("line", Json::Number(span.line.to_string())),
+        ("column", Json::Number(span.column.to_string())),
+        ("start", Json::Number(span.start.to_string())),
+        ("end", Json::Number(span.end.to_string())),
+    ])
+}
+
+/// The token stream as `[{ "type", "text", "span" }, ...]`.
+pub fn tokensJson(tokens: &[(Token, String, Span)]) -> Json {
+    Json::Array(
+        tokens
+            .iter()
+            .map(|(token, text, span)| {
+                Json::object(vec![
+                    ("type", Json::String(format!("{:?}", token))),
+                    ("text", Json::string(text)),
+                    ("span", spanJson(span)),
+                ])
+            })
+            .collect(),
+    )
+}
+
+/// The whole program, one object per statement.
+pub fn astJson(program: &[Statement]) -> Json {
+    Json::Array(program.iter().map(statementJson).collect())
+}
+
+fn statementsJson(statements: &[Statement]) -> Json {
+    Json::Array(statements.iter().map(statementJson).collect())
+}
+
+pub fn statementJson(statement: &Statement) -> Json {
+    let mut fields: Vec<(&str, Json)> = match statement {
+        Statement::VarDec { kind, name, datatype, value, doc, .. } => vec![
+            ("type", Json::string("VarDec")),
+            ("kind", Json::String(format!("{:?}", kind))),
+            ("name", Json::string(name)),
+            ("datatype", Json::optional(datatype)),
+            ("value", expressionJson(value)),
+            ("doc", Json::optional(doc)),
+        ],
+        Statement::VarSet { name, value, .. } => vec![
+            ("type", Json::string("VarSet")),
+            ("name", Json::string(name)),
+            ("value", expressionJson(value)),
+        ],
+        Statement::FieldSet { name, path, value, .. } => vec![
+            ("type", Json::string("FieldSet")),
+            ("name", Json::string(name)),
+            ("path", Json::Array(path.iter().map(|p| Json::string(p)).collect())),
+            ("value", expressionJson(value)),
+        ],
+        Statement::Print { values, target, .. } => vec![
+            ("type", Json::string("Print")),
+            ("values", Json::Array(values.iter().map(expressionJson).collect())),
+            ("target", target.as_ref().map(expressionJson).unwrap_or(Json::Null)),
+        ],
+        Statement::Append { value, target, .. } => vec![
+            ("type", Json::string("Append")),
+            ("value", expressionJson(value)),
+            ("target", expressionJson(target)),
+        ],
+        Statement::Read { name, source, .. } => vec![
+            ("type", Json::string("Read")),
+            ("name", Json::string(name)),
+            ("source", source.as_ref().map(expressionJson).unwrap_or(Json::Null)),
+        ],
+        Statement::Expression { value, .. } => vec![
+            ("type", Json::string("Expression")),
+            ("value", expressionJson(value)),
+        ],
+        Statement::StructDec { name, fields, doc, .. } => vec![
+            ("type", Json::string("StructDec")),
+            ("name", Json::string(name)),
+            ("fields", Json::Array(fields.iter().map(|(field, datatype)| Json::object(vec![
+                ("name", Json::string(field)),
+                ("datatype", Json::string(datatype)),
+            ])).collect())),
+            ("doc", Json::optional(doc)),
+        ],
+        Statement::EnumDec { name, variants, doc, .. } => vec![
+            ("type", Json::string("EnumDec")),
+            ("name", Json::string(name)),
+            ("variants", Json::Array(variants.iter().map(|(variant, payload)| Json::object(vec![
+                ("name", Json::string(variant)),
+                ("payload", Json::Array(payload.iter().map(|t| Json::string(t)).collect())),
+            ])).collect())),
+            ("doc", Json::optional(doc)),
+        ],
+        Statement::If { condition, body, otherwise, .. } => vec![
+            ("type", Json::string("If")),
+            ("condition", expressionJson(condition)),
+            ("body", statementsJson(body)),
+            ("otherwise", otherwise.as_deref().map_or(Json::Null, statementsJson)),
+        ],
+        Statement::Unless { condition, body, .. } => vec![
+            ("type", Json::string("Unless")),
+            ("condition", expressionJson(condition)),
+            ("body", statementsJson(body)),
+        ],
+        Statement::While { condition, body, .. } => vec![
+            ("type", Json::string("While")),
+            ("condition", expressionJson(condition)),
+            ("body", statementsJson(body)),
+        ],
+        Statement::Until { condition, body, .. } => vec![
+            ("type", Json::string("Until")),
+            ("condition", expressionJson(condition)),
+            ("body", statementsJson(body)),
+        ],
+        Statement::For { init, condition, step, body, .. } => vec![
+            ("type", Json::string("For")),
+            ("init", init.as_deref().map_or(Json::Null, statementJson)),
+            ("condition", condition.as_ref().map_or(Json::Null, expressionJson)),
+            ("step", step.as_deref().map_or(Json::Null, statementJson)),
+            ("body", statementsJson(body)),
+        ],
+    };
+    fields.push(("span", spanJson(statement.span())));
+    Json::object(fields)
+}
+
+pub fn expressionJson(expression: &Expression) -> Json {
+    let mut fields: Vec<(&str, Json)> = match &expression.kind {
+        ExpressionKind::Number(value) => vec![
+            ("type", Json::string("Number")),
+            ("datatype", Json::string(numberType(value))),
+            ("value", Json::Number(numberText(value))),
+        ],
+        ExpressionKind::String(text) => vec![
+            ("type", Json::string("String")),
+            ("value", Json::string(text)),
+        ],
+        ExpressionKind::Boolean(b) => vec![
+            ("type", Json::string("Boolean")),
+            ("value", Json::Bool(*b)),
+        ],
+        ExpressionKind::Null => vec![("type", Json::string("Null"))],
+        ExpressionKind::Variable { name, .. } => vec![
+            ("type", Json::string("Variable")),
+            ("name", Json::string(name)),
+        ],
+        ExpressionKind::BinaryOp { op, left, right } => vec![
+            ("type", Json::string("BinaryOp")),
+            ("op", Json::String(format!("{:?}", op))),
+            ("left", expressionJson(left)),
+            ("right", expressionJson(right)),
+        ],
+        ExpressionKind::StructLiteral { name, fields } => vec![
+            ("type", Json::string("StructLiteral")),
+            ("name", Json::string(name)),
+            ("fields", Json::Array(fields.iter().map(|(field, value)| Json::object(vec![
+                ("name", Json::string(field)),
+                ("value", expressionJson(value)),
+            ])).collect())),
+        ],
+        ExpressionKind::FieldAccess { object, field, optional } => vec![
+            ("type", Json::string("FieldAccess")),
+            ("object", expressionJson(object)),
+            ("field", Json::string(field)),
+            ("optional", Json::Bool(*optional)),
+        ],
+        ExpressionKind::EnumVariant { enumName, variant, payload } => vec![
+            ("type", Json::string("EnumVariant")),
+            ("enum", Json::string(enumName)),
+            ("variant", Json::string(variant)),
+            ("payload", Json::Array(payload.iter().map(expressionJson).collect())),
+        ],
+        ExpressionKind::Call { name, args } => vec![
+            ("type", Json::string("Call")),
+            ("name", Json::string(name)),
+            ("args", Json::Array(args.iter().map(expressionJson).collect())),
+        ],
+        ExpressionKind::Match { subject, arms } => vec![
+            ("type", Json::string("Match")),
+            ("subject", expressionJson(subject)),
+            ("arms", Json::Array(arms.iter().map(|(pattern, value)| Json::object(vec![
+                ("type", Json::string("Arm")),
+                ("pattern", patternJson(pattern)),
+                ("value", expressionJson(value)),
+            ])).collect())),
+        ],
+    };
+    fields.push(("span", spanJson(&expression.span)));
+    Json::object(fields)
+}
+
+fn patternJson(pattern: &Pattern) -> Json {
+    match pattern {
+        Pattern::Wildcard => Json::object(vec![("type", Json::string("Wildcard"))]),
+        Pattern::Binding(name) => Json::object(vec![
+            ("type", Json::string("Binding")),
+            ("name", Json::string(name)),
+        ]),
+        Pattern::Literal(value) => Json::object(vec![
+            ("type", Json::string("Literal")),
+            ("value", expressionJson(value)),
+        ]),
+        Pattern::Range { start, end, inclusive } => Json::object(vec![
+            ("type", Json::string("Range")),
+            ("start", expressionJson(start)),
+            ("end", expressionJson(end)),
+            ("inclusive", Json::Bool(*inclusive)),
+        ]),
+        Pattern::Variant { enumName, variant, payload } => Json::object(vec![
+            ("type", Json::string("Variant")),
+            ("enum", Json::string(enumName)),
+            ("variant", Json::string(variant)),
+            ("payload", Json::Array(payload.iter().map(patternJson).collect())),
+        ]),
+    }
+}
+
+// The Jade type name of a number literal's value
+fn numberType(value: &Value) -> &'static str {
+    match value {
+        Value::Int8(_) => "int8",
+        Value::UInt8(_) => "uint8",
+        Value::Int16(_) => "int16",
+        Value::UInt16(_) => "uint16",
+        Value::Int32(_) => "int32",
+        Value::UInt32(_) => "uint32",
+        Value::Int64(_) => "int64",
+        Value::UInt64(_) => "uint64",
+        Value::Float32(_) => "float32",
+        _ => "float64",
+    }
+}
+
+// A number literal's value as JSON number text
+fn numberText(value: &Value) -> String {
+    match value {
+        Value::Int8(v) => v.to_string(),
+        Value::UInt8(v) => v.to_string(),
+        Value::Int16(v) => v.to_string(),
+        Value::UInt16(v) => v.to_string(),
+        Value::Int32(v) => v.to_string(),
+        Value::UInt32(v) => v.to_string(),
+        Value::Int64(v) => v.to_string(),
+        Value::UInt64(v) => v.to_string(),
+        Value::Float32(v) => format!("{:?}", v),
+        Value::Float(v) | Value::Float64(v) => format!("{:?}", v),
+        _ => String::from("null"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lexCode;
+    use crate::parser::parseCode;
+
+    #[test]
+    fn tokenJsonIsStable() {
+        let tokens = lexCode(String::from("a ->;"), "test.bg").unwrap();
+        let json = tokensJson(&tokens[..1]).render();
+        assert_eq!(json, r#"[
+  {
+    "type": "Name",
+    "text": "a",
+    "span": {
+      "file": "test.bg",
+      "line": 1,
+      "column": 1,
+      "start": 0,
+      "end": 1
+    }
+  }
+]"#);
+    }
+
+    #[test]
+    fn astTreeShowsNesting() {
+        let program = parseCode(lexCode(String::from("var a int = 1 + 2;"), "test.bg").unwrap()).unwrap();
+        assert_eq!(astJson(&program).tree(), "\
+VarDec kind=\"Var\" name=\"a\" datatype=\"int\" @1:1
+  value: BinaryOp op=\"Add\" @1:13
+    left: Number datatype=\"int64\" value=1 @1:13
+    right: Number datatype=\"int64\" value=2 @1:17
+");
+    }
+
+    #[test]
+    fn stringsAreEscaped() {
+        assert_eq!(Json::string("say \"hi\"\n").render(), r#""say \"hi\"\n""#);
+    }
+}
@@ -1,151 +1,334 @@
-use crate::parser::{Expression, BinaryOperator};
+use crate::parser::{Expression, ExpressionKind, BinaryOperator, Pattern};
 use crate::runtime::{Runtime, Value};
-use crate::jadeErrors::{valueError, variableNotFoundError};
+use std::cmp::Ordering;
+use crate::jadeErrors::{
+    divisionByZeroError, enumNotFoundError, fieldNotFoundError, missingFieldError, noMatchingArmError,
+    nullOperandError, overflowError, payloadCountError, structNotFoundError, suggest, unsupportedOperationError,
+    unsupportedOperatorError, valueError, variantNotFoundError, JadeError,
+};
 
 #[allow(dead_code, unreachable_patterns)]
-pub fn evaluate(expr: &Expression, runtime: &Runtime, expected_type: Option<String> ) -> Value {
-    
-    let val = match expr {
-        Expression::String(val) => Value::String(val.clone()),
+pub fn evaluate(expr: &Expression, runtime: &mut Runtime, expected_type: Option<String> ) -> Result<Value, JadeError> {
+    // Errors point at the innermost expression that caused them
+    evaluateKind(expr, runtime, expected_type).map_err(|e| e.at(expr.span.clone()))
+}
+
+fn evaluateKind(expr: &Expression, runtime: &mut Runtime, expected_type: Option<String>) -> Result<Value, JadeError> {
+    let val = match &expr.kind {
+        ExpressionKind::String(val) => Value::String(val.clone()),
         
-        Expression::Float(val) => {
-            val.to_string().parse::<f64>().map(Value::Float64).unwrap_or(Value::Null)
+        ExpressionKind::Number(val) => val.clone(),
+
+        ExpressionKind::Boolean(val) => Value::Bool(*val),
+
+        ExpressionKind::Null => Value::Null,
+
+        ExpressionKind::Variable { name, slot } => {
+            match slot.and_then(|slot| runtime.get_slot(slot)) {
+                Some(value) => value.clone(),
+                None => return Err(runtime.unknown_variable(name)),
+            }
         }
 
-        Expression::Integer(val) => {
-            val.to_string().parse::<i64>().map(Value::Int64).unwrap_or(Value::Null)
+        ExpressionKind::StructLiteral { name, fields } => {
+            let declared = structFields(runtime, name, fields.iter().map(|(f, _)| f))?;
+
+            // Build the fields in declared order, checking each one
+            // against its declared type
+            let mut values: Vec<(String, Value)> = Vec::new();
+            for (field, fieldType) in &declared {
+                match fields.iter().find(|(f, _)| f == field) {
+                    Some((_, fieldExpr)) => {
+                        values.push((field.clone(), evaluate(fieldExpr, runtime, Some(fieldType.clone()))?));
+                    }
+                    None => values.push((field.clone(), missingField(name, field, fieldType)?)),
+                }
+            }
+            Value::Struct(name.clone(), values)
         }
 
-        Expression::Boolean(val) => Value::Bool(*val),
+        ExpressionKind::FieldAccess { object, field, optional } => {
+            fieldValue(evaluate(object, runtime, None)?, field, *optional)?
+        }
+
+        ExpressionKind::EnumVariant { enumName, variant, payload } => {
+            let types = variantTypes(runtime, enumName, variant, payload.len())?;
+
+            // Check each payload value against its declared type
+            let mut values: Vec<Value> = Vec::new();
+            for (valueExpr, valueType) in payload.iter().zip(types) {
+                values.push(evaluate(valueExpr, runtime, Some(valueType))?);
+            }
+            Value::Enum(enumName.clone(), variant.clone(), values)
+        }
+
+        ExpressionKind::Match { subject, arms } => {
+            let subject = evaluate(subject, runtime, None)?;
+
+            // The first arm that matches wins, its bindings only live
+            // while its expression is evaluated
+            let mut result: Option<Result<Value, JadeError>> = None;
+            for (pattern, arm) in arms {
+                let mut bindings: Vec<(String, Value)> = Vec::new();
+                if matchPattern(pattern, &subject, &mut bindings) {
+                    runtime.push_scope();
+                    for (_, value) in bindings {
+                        runtime.bind_variable(value);
+                    }
+                    result = Some(evaluate(arm, runtime, None));
+                    runtime.pop_scope();
+                    break;
+                }
+            }
+
+            result.ok_or_else(|| noMatchingArmError(&subject))??
+        }
 
-        Expression::Variable(name) => {
-            let varVal = runtime.get_variable(name).cloned().unwrap_or({variableNotFoundError(name); Value::Null});
-            return varVal
+        ExpressionKind::Call { name, args } => {
+            let mut values: Vec<Value> = Vec::new();
+            for arg in args {
+                values.push(evaluate(arg, runtime, None)?);
+            }
+            runtime.call(name, values)?
         }
 
-        Expression::BinaryOp { op, left, right } => {
-            let lval = evaluate(left, runtime, None);
-            let rval = evaluate(right, runtime, None);
-            evaluate_binary_op(op, lval, rval)
+        // `??` only looks at the right side when the left is null
+        ExpressionKind::BinaryOp { op: BinaryOperator::NullCoalesce, left, right } => {
+            match evaluate(left, runtime, None)? {
+                Value::Null => evaluate(right, runtime, None)?,
+                other => other,
+            }
         }
 
-        _ => {
-            eprintln!("Unsupported expression: {:?}", expr);
-            Value::Null
+        ExpressionKind::BinaryOp { op, left, right } => {
+            let lval = evaluate(left, runtime, None)?;
+            let rval = evaluate(right, runtime, None)?;
+            evaluate_binary_op(op, lval, rval)?
         }
     };
 
     if let Some(ref t) = expected_type {
         coerce_to_type(val, t)
     } else {
-        val
+        Ok(val)
+    }
+}
+
+/// The declared fields of struct `name`, in order, after checking that
+/// every field in `given` is one of them.
+pub fn structFields<'a>(
+    runtime: &Runtime,
+    name: &str,
+    mut given: impl Iterator<Item = &'a String>,
+) -> Result<Vec<(String, String)>, JadeError> {
+    let declared = runtime.get_struct(name).cloned().ok_or_else(|| structNotFoundError(name))?;
+
+    // Any field the struct doesn't declare
+    if let Some(field) = given.find(|field| !declared.iter().any(|(f, _)| f == *field)) {
+        return Err(suggest(fieldNotFoundError(name, field), field, declared.iter().map(|(f, _)| f)));
+    }
+    Ok(declared)
+}
+
+/// The value of a field left out of a struct literal. Nullable fields
+/// can be left out and are null, the rest have to be given.
+pub fn missingField(name: &str, field: &str, fieldType: &str) -> Result<Value, JadeError> {
+    if fieldType.ends_with('?') {
+        Ok(Value::Null)
+    } else {
+        Err(missingFieldError(name, field))
+    }
+}
+
+/// `object.field`, or null for `object?.field` on null.
+pub fn fieldValue(object: Value, field: &str, optional: bool) -> Result<Value, JadeError> {
+    match object {
+        Value::Null if optional => Ok(Value::Null),
+        Value::Struct(structName, fields) => match fields.iter().find(|(f, _)| f == field) {
+            Some((_, v)) => Ok(v.clone()),
+            None => {
+                let error = fieldNotFoundError(&structName, field);
+                Err(suggest(error, field, fields.iter().map(|(f, _)| f)))
+            }
+        },
+        other => Err(JadeError::Type(format!("Can't access field '{}' on {:?}", field, other).into())),
     }
 }
 
-fn coerce_to_type(val: Value, type_str: &str) -> Value {
+/// The payload types of `enumName::variant`, after checking that it
+/// takes `count` values.
+pub fn variantTypes(runtime: &Runtime, enumName: &str, variant: &str, count: usize) -> Result<Vec<String>, JadeError> {
+    let variants = runtime.get_enum(enumName).ok_or_else(|| enumNotFoundError(enumName))?;
+    let (_, types) = variants
+        .iter()
+        .find(|(v, _)| v == variant)
+        .cloned()
+        .ok_or_else(|| variantNotFoundError(enumName, variant))?;
+    if types.len() != count {
+        return Err(payloadCountError(enumName, variant, types.len(), count));
+    }
+    Ok(types)
+}
+
+/// Tests a value against a pattern, collecting any names it binds.
+pub fn matchPattern(pattern: &Pattern, value: &Value, bindings: &mut Vec<(String, Value)>) -> bool {
+    match pattern {
+        Pattern::Wildcard => true,
+        Pattern::Binding(name) => {
+            bindings.push((name.clone(), value.clone()));
+            true
+        }
+        Pattern::Literal(literal) => match (&literal.kind, value) {
+            (ExpressionKind::String(a), Value::String(b)) => a == b,
+            (ExpressionKind::Boolean(a), Value::Bool(b)) => a == b,
+            (ExpressionKind::Number(_), _) => {
+                numericValue(value) == literalNumber(literal)
+            }
+            _ => false,
+        },
+        Pattern::Range { start, end, inclusive } => {
+            match (numericValue(value), literalNumber(start), literalNumber(end)) {
+                (Some(v), Some(start), Some(end)) => {
+                    start <= v && (v < end || (*inclusive && v == end))
+                }
+                _ => false,
+            }
+        }
+        Pattern::Variant { enumName, variant, payload } => match value {
+            Value::Enum(e, v, values) if e == enumName && v == variant && values.len() == payload.len() => {
+                payload
+                    .iter()
+                    .zip(values)
+                    .all(|(p, v)| matchPattern(p, v, bindings))
+            }
+            _ => false,
+        },
+    }
+}
+
+// Numbers from any width, for comparing against patterns
+fn numericValue(value: &Value) -> Option<f64> {
     use Value::*;
-    match (type_str, val) {
+    match *value {
+        Int8(v) => Some(v as f64),
+        UInt8(v) => Some(v as f64),
+        Int16(v) => Some(v as f64),
+        UInt16(v) => Some(v as f64),
+        Int32(v) => Some(v as f64),
+        UInt32(v) => Some(v as f64),
+        Int64(v) => Some(v as f64),
+        UInt64(v) => Some(v as f64),
+        Float(v) | Float64(v) => Some(v),
+        Float32(v) => Some(v as f64),
+        _ => None,
+    }
+}
+
+fn literalNumber(literal: &Expression) -> Option<f64> {
+    match &literal.kind {
+        ExpressionKind::Number(v) => numericValue(v),
+        _ => None,
+    }
+}
+
+pub fn coerce_to_type(val: Value, type_str: &str) -> Result<Value, JadeError> {
+    use Value::*;
+
+    // Nullable types take null or anything the plain type takes
+    if let Some(inner) = type_str.strip_suffix('?') {
+        return match val {
+            Null => Ok(Null),
+            other => coerce_to_type(other, inner),
+        };
+    }
+
+    let outOfRange = |v: i64| JadeError::Type(format!("{} doesn't fit in {}", v, type_str).into());
+
+    let coerced = match (type_str, val) {
         
         // SCALAR Values
         ("$", Int64(v)) => {
-            let returnVal = if -128 <= v && v <= 127 {
+            if (-128..=127).contains(&v) {
                 Int8(v as i8)
-            } else if v >= 0 && v <= 255 {
+            } else if (0..=255).contains(&v) {
                 UInt8(v as u8)
-            } else if -32768 <= v && v <= 32767 {
+            } else if (-32768..=32767).contains(&v) {
                 Int16(v as i16)
-            } else if v >= 0 && v <= 65535 {
+            } else if (0..=65535).contains(&v) {
                 UInt16(v as u16)
-            } else if -2147483648 <= v && v <= 2147483647 {
+            } else if (-2147483648..=2147483647).contains(&v) {
                 Int32(v as i32)
-            } else if v >= 0 && v <= 4294967 {
+            } else if (0..=4294967295).contains(&v) {
                 UInt32(v as u32)
-            } else if -9223372036854775808 <= v && v <= -9223372036854775807 {
-                Int64(v as i64)
             } else {
-                UInt64(v as u64)
-            };
-
-            returnVal
+                Int64(v)
+            }
         },
 
         ("$", UInt64(v)) => {
-            let returnVal = if v <= 255 {
+            if v <= 255 {
                 UInt8(v as u8)
             } else if v <= 65535 {
                 UInt16(v as u16)
-            } else if v <= 4294967 {
+            } else if v <= 4294967295 {
                 UInt32(v as u32)
             } else {
-                UInt64(v as u64)
-            };
-
-            returnVal
+                UInt64(v)
+            }
         },
 
-        ("$", Float64(v)) => Float64(v as f64),
-        ("$", Bool(v)) => Bool(v as bool),
-        ("$", String(v)) => String(v as std::string::String),
+        ("$", Float64(v)) => Float64(v),
+        ("$", Bool(v)) => Bool(v),
+        ("$", String(v)) => String(v),
 
-        ("int", Int8(v)) => {
-            let returnVal = Int8(v as i8);
-            returnVal
-        },
+        ("int", Int8(v)) => Int8(v),
 
         ("int", UInt8(v)) => {
-            let returnVal = if v <= 127 {
+            if v <= 127 {
                 Int8(v as i8)
             } else {
-                UInt8(v as u8)
-            };
-
-                returnVal
+                UInt8(v)
+            }
         },        
 
         ("int", Int16(v)) => {
-            let returnVal = if -128 <= v && v <= 127 {
-                    Int8(v as i8)
-                } else if v >= 0 && v <= 255 {
-                    UInt8(v as u8)
-                } else {
-                    Int16(v as i16)
-                };
-
-                returnVal
+            if (-128..=127).contains(&v) {
+                Int8(v as i8)
+            } else if (0..=255).contains(&v) {
+                UInt8(v as u8)
+            } else {
+                Int16(v)
+            }
         },
 
         ("int", UInt16(v)) => {
-            let returnVal = if v <= 127 {
+            if v <= 127 {
                 Int8(v as i8)
             } else if v <= 255 {
                 UInt8(v as u8)
             } else if v <= 32767 {
                 Int16(v as i16)
             } else {
-                UInt16(v as u16)
-            };
-
-                returnVal
+                UInt16(v)
+            }
         },
 
         ("int", Int32(v)) => {
-        let returnVal = if -128 <= v && v <= 127 {
+            if (-128..=127).contains(&v) {
                 Int8(v as i8)
-            } else if v >= 0 && v <= 255 {
+            } else if (0..=255).contains(&v) {
                 UInt8(v as u8)
-            } else if -32768 <= v && v <= 32767 {
+            } else if (-32768..=32767).contains(&v) {
                 Int16(v as i16)
-            } else if v >= 0 && v <= 65535 {
+            } else if (0..=65535).contains(&v) {
                 UInt16(v as u16)
             } else {
-                Int32(v as i32)
-            };
-
-            returnVal
+                Int32(v)
+            }
         },
 
         ("int", UInt32(v)) => {
-            let returnVal = if v <= 127 {
+            if v <= 127 {
                 Int8(v as i8)
             } else if v <= 255 {
                 UInt8(v as u8)
@@ -156,35 +339,31 @@ fn coerce_to_type(val: Value, type_str: &str) -> Value {
             } else if v <= 2147483647 {
                 Int32(v as i32)
             } else {
-                UInt32(v as u32)
-            };
-
-                returnVal
+                UInt32(v)
+            }
         },
 
         // INT Types
         ("int", Int64(v)) => {
-        let returnVal = if -128 <= v && v <= 127 {
+            if (-128..=127).contains(&v) {
                 Int8(v as i8)
-            } else if v >= 0 && v <= 255 {
+            } else if (0..=255).contains(&v) {
                 UInt8(v as u8)
-            } else if -32768 <= v && v <= 32767 {
+            } else if (-32768..=32767).contains(&v) {
                 Int16(v as i16)
-            } else if v >= 0 && v <= 65535 {
+            } else if (0..=65535).contains(&v) {
                 UInt16(v as u16)
-            } else if -2147483648 <= v && v <= 2147483647 {
+            } else if (-2147483648..=2147483647).contains(&v) {
                 Int32(v as i32)
-            } else if v >= 0 && v <= 4294967 {
+            } else if (0..=4294967295).contains(&v) {
                 UInt32(v as u32)
             } else {
-                Int64(v as i64)
-            };
-
-            returnVal
+                Int64(v)
+            }
         },
 
         ("int", UInt64(v)) => {
-            let returnVal = if v <= 127 {
+            if v <= 127 {
                 Int8(v as i8)
             } else if v <= 255 {
                 UInt8(v as u8)
@@ -194,141 +373,91 @@ fn coerce_to_type(val: Value, type_str: &str) -> Value {
                 UInt16(v as u16)
             } else if v <= 2147483647 {
                 Int32(v as i32)
-            } else if v <= 4294967 {
+            } else if v <= 4294967295 {
                 UInt32(v as u32)
             } else if v <= 9223372036854775807 {
                 Int64(v as i64)
             } else {
-                UInt64(v as u64)
-            };
-
-                returnVal
+                UInt64(v)
+            }
         },
 
-        ("int8", Int64(v))     => Int8(v as i8),
-        ("uint8", Int64(v))    => UInt8(v as u8),
-        ("int16", Int64(v))    => Int16(v as i16),
-        ("uint16", Int64(v))   => UInt16(v as u16),
-        ("int32", Int64(v))    => Int32(v as i32),
-        ("uint32", Int64(v))   => UInt32(v as u32),
-        ("uint64", Int64(v))   => UInt64(v as u64),
-
-        ("int8", Int8(v))     => Int8(v as i8),
-        ("uint8", UInt8(v))    => UInt8(v as u8),
-        ("int16", Int16(v))    => Int16(v as i16),
-        ("uint16", UInt16(v))   => UInt16(v as u16),
-        ("int32", Int32(v))    => Int32(v as i32),
-        ("uint32", UInt32(v))   => UInt32(v as u32),
-        ("int64", Int64(v))    => Int64(v as i64),
-        ("uint64", UInt64(v))   => UInt64(v as u64),
-
-        (_, other) => {
-            valueError(type_str, other);
-            Null
-        }
-    }
+        // Plain number literals are int64, they have to fit the
+        // declared width instead of wrapping into it
+        ("int8", Int64(v))     => Int8(i8::try_from(v).map_err(|_| outOfRange(v))?),
+        ("uint8", Int64(v))    => UInt8(u8::try_from(v).map_err(|_| outOfRange(v))?),
+        ("int16", Int64(v))    => Int16(i16::try_from(v).map_err(|_| outOfRange(v))?),
+        ("uint16", Int64(v))   => UInt16(u16::try_from(v).map_err(|_| outOfRange(v))?),
+        ("int32", Int64(v))    => Int32(i32::try_from(v).map_err(|_| outOfRange(v))?),
+        ("uint32", Int64(v))   => UInt32(u32::try_from(v).map_err(|_| outOfRange(v))?),
+        ("uint64", Int64(v))   => UInt64(u64::try_from(v).map_err(|_| outOfRange(v))?),
+
+        ("int8", Int8(v))     => Int8(v),
+        ("uint8", UInt8(v))    => UInt8(v),
+        ("int16", Int16(v))    => Int16(v),
+        ("uint16", UInt16(v))   => UInt16(v),
+        ("int32", Int32(v))    => Int32(v),
+        ("uint32", UInt32(v))   => UInt32(v),
+        ("int64", Int64(v))    => Int64(v),
+        ("uint64", UInt64(v))   => UInt64(v),
+
+        ("float", Float64(v))   => Float64(v),
+        ("float64", Float64(v)) => Float64(v),
+        ("float32", Float64(v)) => Float32(v as f32),
+        ("float", Float32(v))   => Float32(v),
+        ("float32", Float32(v)) => Float32(v),
+        ("bool", Bool(v))       => Bool(v),
+        ("string", String(v))   => String(v),
+        ("sink", String(v))     => String(v),
+
+        // STRUCTS and ENUMS keep their values, they were checked when built
+        (t, Struct(name, fields)) if t == name => Struct(name, fields),
+        (t, Enum(name, variant, payload)) if t == name => Enum(name, variant, payload),
+
+        // `$` takes whatever it's given
+        ("$", other) => other,
+
+        (_, other) => return Err(valueError(type_str, other)),
+    };
+
+    Ok(coerced)
 }
 
-fn evaluate_binary_op(op: &BinaryOperator, l: Value, r: Value) -> Value {
+pub fn evaluate_binary_op(op: &BinaryOperator, l: Value, r: Value) -> Result<Value, JadeError> {
     use Value::*;
-    match op {
-        BinaryOperator::Add => match (l.clone(), r.clone()) {
-            // Int8
-            (Int8(a), Int8(b))          => Int8(a + b),
-            (Int8(a), UInt8(b))         => Int8(a + b as i8),
-            (Int8(a), Int16(b))        => Int8(a + b as i8),
-            (Int8(a), UInt16(b))       => Int8(a + b as i8),
-            (Int8(a), Int32(b))        => Int8(a + b as i8),
-            (Int8(a), UInt32(b))       => Int8(a + b as i8),
-            (Int8(a), Int64(b))        => Int8(a + b as i8),
-            (Int8(a), UInt64(b))       => Int8(a + b as i8),
-            (Int8(a), Float32(b))      => Int8(a + b.round() as i8),
-            (Int8(a), Float64(b))      => Int8(a + b.round() as i8),
-
-            // Uint8
-            (UInt8(a), UInt8(b))        => UInt8(a + b),
-            (UInt8(a), Int8(b))         => UInt8(a + b as u8),
-            (UInt8(a), Int16(b))       => UInt8(a + b as u8),
-            (UInt8(a), UInt16(b))      => UInt8(a + b as u8),
-            (UInt8(a), Int32(b))       => UInt8(a + b as u8),
-            (UInt8(a), UInt32(b))      => UInt8(a + b as u8),
-            (UInt8(a), Int64(b))       => UInt8(a + b as u8),
-            (UInt8(a), UInt64(b))      => UInt8(a + b as u8),
-            (UInt8(a), Float32(b))     => UInt8(a + b.round() as u8),
-            (UInt8(a), Float64(b))     => UInt8(a + b.round() as u8),
-
-            // Int16
-            (Int16(a), Int16(b))      => Int16(a + b),
-            (Int16(a), Int8(b))        => Int16(a + b as i16),
-            (Int16(a), UInt8(b))       => Int16(a + b as i16),
-            (Int16(a), UInt16(b))     => Int16(a + b as i16),
-            (Int16(a), Int32(b))      => Int16(a + b as i16),
-            (Int16(a), UInt32(b))     => Int16(a + b as i16),
-            (Int16(a), Int64(b))      => Int16(a + b as i16),
-            (Int16(a), UInt64(b))     => Int16(a + b as i16),
-            (Int16(a), Float32(b))    => Int16(a + b.round() as i16),
-            (Int16(a), Float64(b))    => Int16(a + b.round() as i16),
-
-            // UInt16
-            (UInt16(a), UInt16(b))    => UInt16(a + b),
-            (UInt16(a), Int8(b))       => UInt16(a + b as u16),
-            (UInt16(a), UInt8(b))      => UInt16(a + b as u16),
-            (UInt16(a), Int16(b))     => UInt16(a + b as u16),
-            (UInt16(a), Int32(b))     => UInt16(a + b as u16),
-            (UInt16(a), UInt32(b))    => UInt16(a + b as u16),
-            (UInt16(a), Int64(b))     => UInt16(a + b as u16),
-            (UInt16(a), UInt64(b))    => UInt16(a + b as u16),
-            (UInt16(a), Float32(b))   => UInt16(a + b.round() as u16),
-            (UInt16(a), Float64(b))   => UInt16(a + b.round() as u16),
-
-            // Int32
-            (Int32(a), Int32(b))      => Int32(a + b),
-            (Int32(a), Int8(b))        => Int32(a + b as i32),
-            (Int32(a), UInt8(b))       => Int32(a + b as i32),
-            (Int32(a), Int16(b))      => Int32(a + b as i32),
-            (Int32(a), UInt16(b))     => Int32(a + b as i32),
-            (Int32(a), UInt32(b))     => Int32(a + b as i32),
-            (Int32(a), Int64(b))      => Int32(a + b as i32),
-            (Int32(a), UInt64(b))     => Int32(a + b as i32),
-            (Int32(a), Float32(b))    => Int32(a + b.round() as i32),
-            (Int32(a), Float64(b))    => Int32(a + b.round() as i32),
-
-            // UInt32
-            (UInt32(a), UInt32(b))    => UInt32(a + b),
-            (UInt32(a), Int8(b))       => UInt32(a + b as u32),
-            (UInt32(a), UInt8(b))      => UInt32(a + b as u32),
-            (UInt32(a), Int16(b))     => UInt32(a + b as u32),
-            (UInt32(a), UInt16(b))    => UInt32(a + b as u32),
-            (UInt32(a), Int32(b))     => UInt32(a + b as u32),
-            (UInt32(a), Int64(b))     => UInt32(a + b as u32),
-            (UInt32(a), UInt64(b))    => UInt32(a + b as u32),
-            (UInt32(a), Float32(b))   => UInt32(a + b.round() as u32),
-            (UInt32(a), Float64(b))   => UInt32(a + b.round() as u32),
-
-            // Int64
-            (Int64(a), Int64(b))      => Int64(a + b),
-            (Int64(a), Int8(b))        => Int64(a + b as i64),
-            (Int64(a), UInt8(b))       => Int64(a + b as i64),
-            (Int64(a), Int16(b))      => Int64(a + b as i64),
-            (Int64(a), UInt16(b))     => Int64(a + b as i64),
-            (Int64(a), Int32(b))      => Int64(a + b as i64),
-            (Int64(a), UInt32(b))     => Int64(a + b as i64),
-            (Int64(a), UInt64(b))     => Int64(a + b as i64),
-            (Int64(a), Float32(b))    => Int64(a + b.round() as i64),
-            (Int64(a), Float64(b))    => Int64(a + b.round() as i64),
-
-            // UInt64
-            (UInt64(a), UInt64(b))    => UInt64(a + b),
-            (UInt64(a), Int8(b))       => UInt64(a + b as u64),
-            (UInt64(a), UInt8(b))      => UInt64(a + b as u64),
-            (UInt64(a), Int16(b))     => UInt64(a + b as u64),
-            (UInt64(a), UInt16(b))    => UInt64(a + b as u64),
-            (UInt64(a), Int32(b))     => UInt64(a + b as u64),
-            (UInt64(a), UInt32(b))    => UInt64(a + b as u64),
-            (UInt64(a), Int64(b))     => UInt64(a + b as u64),
-            (UInt64(a), Float32(b))   => UInt64(a + b.round() as u64),
-            (UInt64(a), Float64(b))   => UInt64(a + b.round() as u64),
 
+    // Null only makes sense in equality checks
+    if matches!(l, Null) || matches!(r, Null) {
+        return match op {
+            BinaryOperator::Equal => Ok(Bool(matches!((&l, &r), (Null, Null)))),
+            BinaryOperator::NotEqual => Ok(Bool(!matches!((&l, &r), (Null, Null)))),
+            _ => Err(nullOperandError(op)),
+        };
+    }
+
+    // Integer math is checked, the result keeps the left side's type.
+    // Both sides are widened first so the right one can't wrap before
+    // the check, a float on the right is rounded.
+    let overflow = || overflowError(op, &l, &r);
+    let arithmetic = matches!(
+        op,
+        BinaryOperator::Add | BinaryOperator::Subtract | BinaryOperator::Multiply | BinaryOperator::Divide
+    );
+    if arithmetic && let Some(a) = integerValue(&l) && numericValue(&r).is_some() {
+        let b = roundedValue(&r).ok_or_else(overflow)?;
+        let result = match op {
+            BinaryOperator::Add => a.checked_add(b),
+            BinaryOperator::Subtract => a.checked_sub(b),
+            BinaryOperator::Multiply => a.checked_mul(b),
+            // Integer division needs a non-zero divisor, after rounding
+            _ if b == 0 => return Err(divisionByZeroError(&l)),
+            _ => a.checked_div(b),
+        };
+        return result.and_then(|result| sameWidth(&l, result)).ok_or_else(overflow);
+    }
+
+    let result = match op {
+        BinaryOperator::Add => match (l.clone(), r.clone()) {
             // Float32s
             (Float32(a), Float32(b))  => Float32(a + b),
             (Float32(a), Int8(b))      => Float32(a + b as f32),
@@ -352,110 +481,10 @@ fn evaluate_binary_op(op: &BinaryOperator, l: Value, r: Value) -> Value {
             (Float64(a), Int64(b))    => Float64(a + b as f64),
             (Float64(a), UInt64(b))   => Float64(a + b as f64),
             (Float64(a), Float32(b))  => Float64(a + b as f64),
-            _ => {
-                eprintln!("Add not supported between given types: {:?} and {:?}", l, r);
-                Value::Null
-            }
+            _ => return Err(unsupportedOperationError(op, &l, &r)),
         },
 
         BinaryOperator::Subtract => match (l.clone(), r.clone()) {
-
-            // Int8
-            (Int8(a), Int8(b))          => Int8(a - b),
-            (Int8(a), UInt8(b))         => Int8(a - b as i8),
-            (Int8(a), Int16(b))        => Int8(a - b as i8),
-            (Int8(a), UInt16(b))       => Int8(a - b as i8),
-            (Int8(a), Int32(b))        => Int8(a - b as i8),
-            (Int8(a), UInt32(b))       => Int8(a - b as i8),
-            (Int8(a), Int64(b))        => Int8(a - b as i8),
-            (Int8(a), UInt64(b))       => Int8(a - b as i8),
-            (Int8(a), Float32(b))      => Int8(a - b.round() as i8),
-            (Int8(a), Float64(b))      => Int8(a - b.round() as i8),
-
-            // Uint8
-            (UInt8(a), UInt8(b))        => UInt8(a - b),
-            (UInt8(a), Int8(b))         => UInt8(a - b as u8),
-            (UInt8(a), Int16(b))       => UInt8(a - b as u8),
-            (UInt8(a), UInt16(b))      => UInt8(a - b as u8),
-            (UInt8(a), Int32(b))       => UInt8(a - b as u8),
-            (UInt8(a), UInt32(b))      => UInt8(a - b as u8),
-            (UInt8(a), Int64(b))       => UInt8(a - b as u8),
-            (UInt8(a), UInt64(b))      => UInt8(a - b as u8),
-            (UInt8(a), Float32(b))     => UInt8(a - b.round() as u8),
-            (UInt8(a), Float64(b))     => UInt8(a - b.round() as u8),
-
-            // Int16
-            (Int16(a), Int16(b))      => Int16(a - b),
-            (Int16(a), Int8(b))        => Int16(a - b as i16),
-            (Int16(a), UInt8(b))       => Int16(a - b as i16),
-            (Int16(a), UInt16(b))     => Int16(a - b as i16),
-            (Int16(a), Int32(b))      => Int16(a - b as i16),
-            (Int16(a), UInt32(b))     => Int16(a - b as i16),
-            (Int16(a), Int64(b))      => Int16(a - b as i16),
-            (Int16(a), UInt64(b))     => Int16(a - b as i16),
-            (Int16(a), Float32(b))    => Int16(a - b.round() as i16),
-            (Int16(a), Float64(b))    => Int16(a - b.round() as i16),
-
-            // UInt16
-            (UInt16(a), UInt16(b))    => UInt16(a - b),
-            (UInt16(a), Int8(b))       => UInt16(a - b as u16),
-            (UInt16(a), UInt8(b))      => UInt16(a - b as u16),
-            (UInt16(a), Int16(b))     => UInt16(a - b as u16),
-            (UInt16(a), Int32(b))     => UInt16(a - b as u16),
-            (UInt16(a), UInt32(b))    => UInt16(a - b as u16),
-            (UInt16(a), Int64(b))     => UInt16(a - b as u16),
-            (UInt16(a), UInt64(b))    => UInt16(a - b as u16),
-            (UInt16(a), Float32(b))   => UInt16(a - b.round() as u16),
-            (UInt16(a), Float64(b))   => UInt16(a - b.round() as u16),
-
-            // Int32
-            (Int32(a), Int32(b))      => Int32(a - b),
-            (Int32(a), Int8(b))        => Int32(a - b as i32),
-            (Int32(a), UInt8(b))       => Int32(a - b as i32),
-            (Int32(a), Int16(b))      => Int32(a - b as i32),
-            (Int32(a), UInt16(b))     => Int32(a - b as i32),
-            (Int32(a), UInt32(b))     => Int32(a - b as i32),
-            (Int32(a), Int64(b))      => Int32(a - b as i32),
-            (Int32(a), UInt64(b))     => Int32(a - b as i32),
-            (Int32(a), Float32(b))    => Int32(a - b.round() as i32),
-            (Int32(a), Float64(b))    => Int32(a - b.round() as i32),
-
-            // UInt32
-            (UInt32(a), UInt32(b))    => UInt32(a - b),
-            (UInt32(a), Int8(b))       => UInt32(a - b as u32),
-            (UInt32(a), UInt8(b))      => UInt32(a - b as u32),
-            (UInt32(a), Int16(b))     => UInt32(a - b as u32),
-            (UInt32(a), UInt16(b))    => UInt32(a - b as u32),
-            (UInt32(a), Int32(b))     => UInt32(a - b as u32),
-            (UInt32(a), Int64(b))     => UInt32(a - b as u32),
-            (UInt32(a), UInt64(b))    => UInt32(a - b as u32),
-            (UInt32(a), Float32(b))   => UInt32(a - b.round() as u32),
-            (UInt32(a), Float64(b))   => UInt32(a - b.round() as u32),
-
-            // Int64
-            (Int64(a), Int64(b))      => Int64(a - b),
-            (Int64(a), Int8(b))        => Int64(a - b as i64),
-            (Int64(a), UInt8(b))       => Int64(a - b as i64),
-            (Int64(a), Int16(b))      => Int64(a - b as i64),
-            (Int64(a), UInt16(b))     => Int64(a - b as i64),
-            (Int64(a), Int32(b))      => Int64(a - b as i64),
-            (Int64(a), UInt32(b))     => Int64(a - b as i64),
-            (Int64(a), UInt64(b))     => Int64(a - b as i64),
-            (Int64(a), Float32(b))    => Int64(a - b.round() as i64),
-            (Int64(a), Float64(b))    => Int64(a - b.round() as i64),
-
-            // UInt64
-            (UInt64(a), UInt64(b))    => UInt64(a - b),
-            (UInt64(a), Int8(b))       => UInt64(a - b as u64),
-            (UInt64(a), UInt8(b))      => UInt64(a - b as u64),
-            (UInt64(a), Int16(b))     => UInt64(a - b as u64),
-            (UInt64(a), UInt16(b))    => UInt64(a - b as u64),
-            (UInt64(a), Int32(b))     => UInt64(a - b as u64),
-            (UInt64(a), UInt32(b))    => UInt64(a - b as u64),
-            (UInt64(a), Int64(b))     => UInt64(a - b as u64),
-            (UInt64(a), Float32(b))   => UInt64(a - b.round() as u64),
-            (UInt64(a), Float64(b))   => UInt64(a - b.round() as u64),
-
             // Float32s
             (Float32(a), Float32(b))  => Float32(a - b),
             (Float32(a), Int8(b))      => Float32(a - b as f32),
@@ -480,110 +509,10 @@ fn evaluate_binary_op(op: &BinaryOperator, l: Value, r: Value) -> Value {
             (Float64(a), UInt64(b))   => Float64(a - b as f64),
             (Float64(a), Float32(b))  => Float64(a - b as f64),
 
-
-            _ => {
-                eprintln!("Subtract not supported between given types: {:?} - {:?}.", l, r);
-                Value::Null
-            }
+            _ => return Err(unsupportedOperationError(op, &l, &r)),
         },
 
         BinaryOperator::Multiply => match (l.clone(), r.clone()) {
-            // Int8
-            (Int8(a), Int8(b))          => Int8(a * b),
-            (Int8(a), UInt8(b))         => Int8(a * b as i8),
-            (Int8(a), Int16(b))        => Int8(a * b as i8),
-            (Int8(a), UInt16(b))       => Int8(a * b as i8),
-            (Int8(a), Int32(b))        => Int8(a * b as i8),
-            (Int8(a), UInt32(b))       => Int8(a * b as i8),
-            (Int8(a), Int64(b))        => Int8(a * b as i8),
-            (Int8(a), UInt64(b))       => Int8(a * b as i8),
-            (Int8(a), Float32(b))      => Int8(a * b.round() as i8),
-            (Int8(a), Float64(b))      => Int8(a * b.round() as i8),
-
-            // Uint8
-            (UInt8(a), UInt8(b))        => UInt8(a * b),
-            (UInt8(a), Int8(b))         => UInt8(a * b as u8),
-            (UInt8(a), Int16(b))       => UInt8(a * b as u8),
-            (UInt8(a), UInt16(b))      => UInt8(a * b as u8),
-            (UInt8(a), Int32(b))       => UInt8(a * b as u8),
-            (UInt8(a), UInt32(b))      => UInt8(a * b as u8),
-            (UInt8(a), Int64(b))       => UInt8(a * b as u8),
-            (UInt8(a), UInt64(b))      => UInt8(a * b as u8),
-            (UInt8(a), Float32(b))     => UInt8(a * b.round() as u8),
-            (UInt8(a), Float64(b))     => UInt8(a * b.round() as u8),
-
-            // Int16
-            (Int16(a), Int16(b))      => Int16(a * b),
-            (Int16(a), Int8(b))        => Int16(a * b as i16),
-            (Int16(a), UInt8(b))       => Int16(a * b as i16),
-            (Int16(a), UInt16(b))     => Int16(a * b as i16),
-            (Int16(a), Int32(b))      => Int16(a * b as i16),
-            (Int16(a), UInt32(b))     => Int16(a * b as i16),
-            (Int16(a), Int64(b))      => Int16(a * b as i16),
-            (Int16(a), UInt64(b))     => Int16(a * b as i16),
-            (Int16(a), Float32(b))    => Int16(a * b.round() as i16),
-            (Int16(a), Float64(b))    => Int16(a * b.round() as i16),
-
-            // UInt16
-            (UInt16(a), UInt16(b))    => UInt16(a * b),
-            (UInt16(a), Int8(b))       => UInt16(a * b as u16),
-            (UInt16(a), UInt8(b))      => UInt16(a * b as u16),
-            (UInt16(a), Int16(b))     => UInt16(a * b as u16),
-            (UInt16(a), Int32(b))     => UInt16(a * b as u16),
-            (UInt16(a), UInt32(b))    => UInt16(a * b as u16),
-            (UInt16(a), Int64(b))     => UInt16(a * b as u16),
-            (UInt16(a), UInt64(b))    => UInt16(a * b as u16),
-            (UInt16(a), Float32(b))   => UInt16(a * b.round() as u16),
-            (UInt16(a), Float64(b))   => UInt16(a * b.round() as u16),
-
-            // Int32
-            (Int32(a), Int32(b))      => Int32(a * b),
-            (Int32(a), Int8(b))        => Int32(a * b as i32),
-            (Int32(a), UInt8(b))       => Int32(a * b as i32),
-            (Int32(a), Int16(b))      => Int32(a * b as i32),
-            (Int32(a), UInt16(b))     => Int32(a * b as i32),
-            (Int32(a), UInt32(b))     => Int32(a * b as i32),
-            (Int32(a), Int64(b))      => Int32(a * b as i32),
-            (Int32(a), UInt64(b))     => Int32(a * b as i32),
-            (Int32(a), Float32(b))    => Int32(a * b.round() as i32),
-            (Int32(a), Float64(b))    => Int32(a * b.round() as i32),
-
-            // UInt32
-            (UInt32(a), UInt32(b))    => UInt32(a * b),
-            (UInt32(a), Int8(b))       => UInt32(a * b as u32),
-            (UInt32(a), UInt8(b))      => UInt32(a * b as u32),
-            (UInt32(a), Int16(b))     => UInt32(a * b as u32),
-            (UInt32(a), UInt16(b))    => UInt32(a * b as u32),
-            (UInt32(a), Int32(b))     => UInt32(a * b as u32),
-            (UInt32(a), Int64(b))     => UInt32(a * b as u32),
-            (UInt32(a), UInt64(b))    => UInt32(a * b as u32),
-            (UInt32(a), Float32(b))   => UInt32(a * b.round() as u32),
-            (UInt32(a), Float64(b))   => UInt32(a * b.round() as u32),
-
-            // Int64
-            (Int64(a), Int64(b))      => Int64(a * b),
-            (Int64(a), Int8(b))        => Int64(a * b as i64),
-            (Int64(a), UInt8(b))       => Int64(a * b as i64),
-            (Int64(a), Int16(b))      => Int64(a * b as i64),
-            (Int64(a), UInt16(b))     => Int64(a * b as i64),
-            (Int64(a), Int32(b))      => Int64(a * b as i64),
-            (Int64(a), UInt32(b))     => Int64(a * b as i64),
-            (Int64(a), UInt64(b))     => Int64(a * b as i64),
-            (Int64(a), Float32(b))    => Int64(a * b.round() as i64),
-            (Int64(a), Float64(b))    => Int64(a * b.round() as i64),
-
-            // UInt64
-            (UInt64(a), UInt64(b))    => UInt64(a * b),
-            (UInt64(a), Int8(b))       => UInt64(a * b as u64),
-            (UInt64(a), UInt8(b))      => UInt64(a * b as u64),
-            (UInt64(a), Int16(b))     => UInt64(a * b as u64),
-            (UInt64(a), UInt16(b))    => UInt64(a * b as u64),
-            (UInt64(a), Int32(b))     => UInt64(a * b as u64),
-            (UInt64(a), UInt32(b))    => UInt64(a * b as u64),
-            (UInt64(a), Int64(b))     => UInt64(a * b as u64),
-            (UInt64(a), Float32(b))   => UInt64(a * b.round() as u64),
-            (UInt64(a), Float64(b))   => UInt64(a * b.round() as u64),
-
             // Float32s
             (Float32(a), Float32(b))  => Float32(a * b),
             (Float32(a), Int8(b))      => Float32(a * b as f32),
@@ -608,109 +537,10 @@ fn evaluate_binary_op(op: &BinaryOperator, l: Value, r: Value) -> Value {
             (Float64(a), UInt64(b))   => Float64(a * b as f64),
             (Float64(a), Float32(b))  => Float64(a * b as f64),
 
-            _ => {
-            eprintln!("Subtract not supported between given types: {:?} - {:?}.", l, r);
-            Value::Null
-            }
+            _ => return Err(unsupportedOperationError(op, &l, &r)),
         },
 
         BinaryOperator::Divide => match (l.clone(), r.clone()) {
-            // Int8
-            (Int8(a), Int8(b))          => Int8(a / b),
-            (Int8(a), UInt8(b))         => Int8(a / b as i8),
-            (Int8(a), Int16(b))        => Int8(a / b as i8),
-            (Int8(a), UInt16(b))       => Int8(a / b as i8),
-            (Int8(a), Int32(b))        => Int8(a / b as i8),
-            (Int8(a), UInt32(b))       => Int8(a / b as i8),
-            (Int8(a), Int64(b))        => Int8(a / b as i8),
-            (Int8(a), UInt64(b))       => Int8(a / b as i8),
-            (Int8(a), Float32(b))      => Int8(a / b.round() as i8),
-            (Int8(a), Float64(b))      => Int8(a / b.round() as i8),
-
-            // Uint8
-            (UInt8(a), UInt8(b))        => UInt8(a / b),
-            (UInt8(a), Int8(b))         => UInt8(a / b as u8),
-            (UInt8(a), Int16(b))       => UInt8(a / b as u8),
-            (UInt8(a), UInt16(b))      => UInt8(a / b as u8),
-            (UInt8(a), Int32(b))       => UInt8(a / b as u8),
-            (UInt8(a), UInt32(b))      => UInt8(a / b as u8),
-            (UInt8(a), Int64(b))       => UInt8(a / b as u8),
-            (UInt8(a), UInt64(b))      => UInt8(a / b as u8),
-            (UInt8(a), Float32(b))     => UInt8(a / b.round() as u8),
-            (UInt8(a), Float64(b))     => UInt8(a / b.round() as u8),
-
-            // Int16
-            (Int16(a), Int16(b))      => Int16(a / b),
-            (Int16(a), Int8(b))        => Int16(a / b as i16),
-            (Int16(a), UInt8(b))       => Int16(a / b as i16),
-            (Int16(a), UInt16(b))     => Int16(a / b as i16),
-            (Int16(a), Int32(b))      => Int16(a / b as i16),
-            (Int16(a), UInt32(b))     => Int16(a / b as i16),
-            (Int16(a), Int64(b))      => Int16(a / b as i16),
-            (Int16(a), UInt64(b))     => Int16(a / b as i16),
-            (Int16(a), Float32(b))    => Int16(a / b.round() as i16),
-            (Int16(a), Float64(b))    => Int16(a / b.round() as i16),
-
-            // UInt16
-            (UInt16(a), UInt16(b))    => UInt16(a / b),
-            (UInt16(a), Int8(b))       => UInt16(a / b as u16),
-            (UInt16(a), UInt8(b))      => UInt16(a / b as u16),
-            (UInt16(a), Int16(b))     => UInt16(a / b as u16),
-            (UInt16(a), Int32(b))     => UInt16(a / b as u16),
-            (UInt16(a), UInt32(b))    => UInt16(a / b as u16),
-            (UInt16(a), Int64(b))     => UInt16(a / b as u16),
-            (UInt16(a), UInt64(b))    => UInt16(a / b as u16),
-            (UInt16(a), Float32(b))   => UInt16(a / b.round() as u16),
-            (UInt16(a), Float64(b))   => UInt16(a / b.round() as u16),
-
-            // Int32
-            (Int32(a), Int32(b))      => Int32(a / b),
-            (Int32(a), Int8(b))        => Int32(a / b as i32),
-            (Int32(a), UInt8(b))       => Int32(a / b as i32),
-            (Int32(a), Int16(b))      => Int32(a / b as i32),
-            (Int32(a), UInt16(b))     => Int32(a / b as i32),
-            (Int32(a), UInt32(b))     => Int32(a / b as i32),
-            (Int32(a), Int64(b))      => Int32(a / b as i32),
-            (Int32(a), UInt64(b))     => Int32(a / b as i32),
-            (Int32(a), Float32(b))    => Int32(a / b.round() as i32),
-            (Int32(a), Float64(b))    => Int32(a / b.round() as i32),
-
-            // UInt32
-            (UInt32(a), UInt32(b))    => UInt32(a / b),
-            (UInt32(a), Int8(b))       => UInt32(a / b as u32),
-            (UInt32(a), UInt8(b))      => UInt32(a / b as u32),
-            (UInt32(a), Int16(b))     => UInt32(a / b as u32),
-            (UInt32(a), UInt16(b))    => UInt32(a / b as u32),
-            (UInt32(a), Int32(b))     => UInt32(a / b as u32),
-            (UInt32(a), Int64(b))     => UInt32(a / b as u32),
-            (UInt32(a), UInt64(b))    => UInt32(a / b as u32),
-            (UInt32(a), Float32(b))   => UInt32(a / b.round() as u32),
-            (UInt32(a), Float64(b))   => UInt32(a / b.round() as u32),
-
-            // Int64
-            (Int64(a), Int64(b))      => Int64(a / b),
-            (Int64(a), Int8(b))        => Int64(a / b as i64),
-            (Int64(a), UInt8(b))       => Int64(a / b as i64),
-            (Int64(a), Int16(b))      => Int64(a / b as i64),
-            (Int64(a), UInt16(b))     => Int64(a / b as i64),
-            (Int64(a), Int32(b))      => Int64(a / b as i64),
-            (Int64(a), UInt32(b))     => Int64(a / b as i64),
-            (Int64(a), UInt64(b))     => Int64(a / b as i64),
-            (Int64(a), Float32(b))    => Int64(a / b.round() as i64),
-            (Int64(a), Float64(b))    => Int64(a / b.round() as i64),
-
-            // UInt64
-            (UInt64(a), UInt64(b))    => UInt64(a / b),
-            (UInt64(a), Int8(b))       => UInt64(a / b as u64),
-            (UInt64(a), UInt8(b))      => UInt64(a / b as u64),
-            (UInt64(a), Int16(b))     => UInt64(a / b as u64),
-            (UInt64(a), UInt16(b))    => UInt64(a / b as u64),
-            (UInt64(a), Int32(b))     => UInt64(a / b as u64),
-            (UInt64(a), UInt32(b))    => UInt64(a / b as u64),
-            (UInt64(a), Int64(b))     => UInt64(a / b as u64),
-            (UInt64(a), Float32(b))   => UInt64(a / b.round() as u64),
-            (UInt64(a), Float64(b))   => UInt64(a / b.round() as u64),
-
             // Float32s
             (Float32(a), Float32(b))  => Float32(a / b),
             (Float32(a), Int8(b))      => Float32(a / b as f32),
@@ -735,15 +565,81 @@ fn evaluate_binary_op(op: &BinaryOperator, l: Value, r: Value) -> Value {
             (Float64(a), UInt64(b))   => Float64(a / b as f64),
             (Float64(a), Float32(b))  => Float64(a / b as f64),
 
-            _ => {
-            eprintln!("Subtract not supported between given types: {:?} - {:?}.", l, r);
-            Value::Null
-            }
+            _ => return Err(unsupportedOperationError(op, &l, &r)),
         }
-        // Handle other ops similarly...
-        _ => {
-            eprintln!("Unsupported binary operator: {:?}", op);
-            Value::Null
+        // Comparisons work across number widths, `===` also needs the
+        // same type on both sides
+        BinaryOperator::Equal => Bool(compareValues(&l, &r) == Some(Ordering::Equal) || l == r),
+        BinaryOperator::NotEqual => Bool(!(compareValues(&l, &r) == Some(Ordering::Equal) || l == r)),
+        BinaryOperator::TrueEquality => Bool(l == r),
+        BinaryOperator::GreaterThan |
+        BinaryOperator::LessThan |
+        BinaryOperator::GreaterOrEqual |
+        BinaryOperator::LessOrEqual => {
+            let ordering = compareValues(&l, &r).ok_or_else(|| unsupportedOperationError(op, &l, &r))?;
+            Bool(match op {
+                BinaryOperator::GreaterThan => ordering == Ordering::Greater,
+                BinaryOperator::LessThan => ordering == Ordering::Less,
+                BinaryOperator::GreaterOrEqual => ordering != Ordering::Less,
+                _ => ordering != Ordering::Greater,
+            })
         }
+
+        // Handle other ops similarly...
+        _ => return Err(unsupportedOperatorError(op)),
+    };
+
+    Ok(result)
+}
+
+// Orders two numbers of any width, or two strings, or two bools
+fn compareValues(l: &Value, r: &Value) -> Option<Ordering> {
+    use Value::*;
+    match (l, r) {
+        (String(a), String(b)) => Some(a.cmp(b)),
+        (Bool(a), Bool(b)) => Some(a.cmp(b)),
+        _ => match (integerValue(l), integerValue(r)) {
+            (Some(a), Some(b)) => Some(a.cmp(&b)),
+            _ => numericValue(l)?.partial_cmp(&numericValue(r)?),
+        },
+    }
+}
+
+// An integer from any width, or a float rounded to one
+fn roundedValue(value: &Value) -> Option<i128> {
+    integerValue(value).or_else(|| {
+        numericValue(value).filter(|v| v.is_finite()).map(|v| v.round() as i128)
+    })
+}
+
+// `value` as an integer of the same type as `like`, if it fits
+fn sameWidth(like: &Value, value: i128) -> Option<Value> {
+    use Value::*;
+    Some(match like {
+        Int8(_) => Int8(value.try_into().ok()?),
+        UInt8(_) => UInt8(value.try_into().ok()?),
+        Int16(_) => Int16(value.try_into().ok()?),
+        UInt16(_) => UInt16(value.try_into().ok()?),
+        Int32(_) => Int32(value.try_into().ok()?),
+        UInt32(_) => UInt32(value.try_into().ok()?),
+        Int64(_) => Int64(value.try_into().ok()?),
+        UInt64(_) => UInt64(value.try_into().ok()?),
+        _ => return None,
+    })
+}
+
+// Integers from any width, so big ones compare exactly
+fn integerValue(value: &Value) -> Option<i128> {
+    use Value::*;
+    match *value {
+        Int8(v) => Some(v as i128),
+        UInt8(v) => Some(v as i128),
+        Int16(v) => Some(v as i128),
+        UInt16(v) => Some(v as i128),
+        Int32(v) => Some(v as i128),
+        UInt32(v) => Some(v as i128),
+        Int64(v) => Some(v as i128),
+        UInt64(v) => Some(v as i128),
+        _ => None,
     }
 }
@@ -0,0 +1,263 @@
+use crate::lexer::{Span, Token};
+
+/// What a `{` was opened for. Blocks (bodies of `if`, `while`, `struct`
+/// and so on) put everything inside on its own indented lines, inline
+/// braces (struct literals and `match` arms) stay on one line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Brace {
+    Block,
+    Inline,
+}
+
+const INDENT: &str = "    ";
+
+/// Lays the tokens of a file out in the canonical Jade style:
+///
+/// - one statement per line, four spaces of indent per block
+/// - `{` on the same line as what opens it, `}` on its own line, with
+///   `else` and `elif` following it on that line
+/// - one space around operators, `=` and `->`, none inside parens or
+///   around `.`, `?.`, `::` and ranges
+/// - blank lines between statements kept, but never more than one and
+///   never at the start or end of a block
+/// - comments kept where they are, trailing ones stay on their line
+///
+/// The tokens have to come from lexCode with comments, and the code has
+/// to parse. Formatting the output again gives the same output.
+pub fn formatCode(source: &str, tokens: &[(Token, String, Span)]) -> String {
+    let mut out = String::new();
+    let mut indent: usize = 0;
+    let mut braces: Vec<Brace> = Vec::new();
+    let mut parens: Vec<Option<String>> = Vec::new(); // The control keyword each `(` follows
+    let mut closedHeader: Option<String> = None; // The keyword of the `(...)` just closed
+
+    let mut breakNext = false; // The next token starts a new line
+    let mut lineStart = true; // Nothing is on the current line yet
+    let mut justOpened = false; // The last line opened a block
+    let mut prevEnd: usize = 0;
+
+    // The last two code tokens, comments aren't counted
+    let mut prevCode: Option<&(Token, String, Span)> = None;
+    let mut prevPrevCode: Option<&(Token, String, Span)> = None;
+    let mut prev: Option<&Token> = None;
+
+    for (idx, token) in tokens.iter().enumerate() {
+        let (kind, text, span) = token;
+        let isComment = matches!(kind, Token::LineComment | Token::BlockComment | Token::DocComment);
+        let gap = if idx == 0 { 0 } else { source[prevEnd..span.start].matches('\n').count() };
+        prevEnd = span.end;
+
+        let closesBlock = *kind == Token::CloseBrace && braces.last() == Some(&Brace::Block);
+        let ownLine = if isComment {
+            gap > 0 || *kind == Token::DocComment
+        } else {
+            breakNext || closesBlock
+        };
+
+        // Start the new line, keeping one blank line if there was any
+        if ownLine && !out.is_empty() && !lineStart {
+            out.push('\n');
+            if gap >= 2 && !justOpened && !closesBlock {
+                out.push('\n');
+            }
+            lineStart = true;
+        }
+
+        if closesBlock {
+            braces.pop();
+            indent = indent.saturating_sub(1);
+        }
+
+        if lineStart {
+            // Lines that carry on inside braces or parens after a comment
+            // sit one level further in
+            let inline = braces.iter().rev().take_while(|b| **b == Brace::Inline).count() + parens.len();
+            out.push_str(&INDENT.repeat(indent + inline));
+        } else if needsSpace(prev, kind) {
+            out.push(' ');
+        }
+        out.push_str(text);
+        lineStart = false;
+        justOpened = false;
+
+        // A comment after the end of a statement doesn't stop the next
+        // statement from starting a new line
+        if isComment {
+            breakNext = breakNext || ownLine || *kind != Token::BlockComment;
+            prev = Some(kind);
+            continue;
+        }
+
+        breakNext = false;
+        match kind {
+            Token::OpenParen => {
+                let keyword = match prevCode {
+                    Some((Token::ControlBlock, keyword, _)) => Some(keyword.clone()),
+                    _ => None,
+                };
+                parens.push(keyword);
+            }
+            Token::CloseParen => closedHeader = parens.pop().flatten(),
+            Token::OpenBrace => {
+                let isBlock = match (prevCode, prevPrevCode) {
+                    (Some((Token::CloseParen, _, _)), _) => closedHeader.as_deref().is_some_and(|k| k != "match"),
+                    (Some((Token::ControlBlock, keyword, _)), _) => keyword == "else",
+                    (_, Some((Token::Struct | Token::Enum, _, _))) => true,
+                    _ => false,
+                };
+                if isBlock {
+                    braces.push(Brace::Block);
+                    indent += 1;
+                    breakNext = true;
+                    justOpened = true;
+                } else {
+                    braces.push(Brace::Inline);
+                }
+            }
+            Token::CloseBrace if closesBlock => {
+                let chained = tokens[idx + 1..]
+                    .iter()
+                    .find(|(t, _, _)| !matches!(t, Token::LineComment | Token::BlockComment | Token::DocComment))
+                    .is_some_and(|(t, word, _)| *t == Token::ControlBlock && (word == "else" || word == "elif"));
+                breakNext = !chained;
+            }
+            Token::CloseBrace => {
+                braces.pop();
+            }
+            Token::Semicolon | Token::Comma => {
+                breakNext = parens.is_empty() && braces.last() != Some(&Brace::Inline);
+            }
+            _ => {}
+        }
+
+        prevPrevCode = prevCode;
+        prevCode = Some(token);
+        prev = Some(kind);
+    }
+
+    let formatted = out.trim_end();
+    if formatted.is_empty() {
+        String::new()
+    } else {
+        format!("{}\n", formatted)
+    }
+}
+
+// Whether two tokens on the same line get a space between them
+fn needsSpace(prev: Option<&Token>, current: &Token) -> bool {
+    use Token::*;
+    match (prev, current) {
+        (None, _) => false,
+        (Some(OpenParen), _) => false,
+        (Some(OpenBrace), CloseBrace) => false,
+        (_, CloseParen | Comma | Semicolon | Question) => false,
+        (Some(Dot | OptionalDot | DoubleColon | Range | RangeInclusive), _) => false,
+        (_, Dot | OptionalDot | DoubleColon | Range | RangeInclusive) => false,
+        // `Enum::Variant(...)`, `Variant(type)` and `print(...)`, but `if (...)`
+        (Some(Name | BuiltIn), OpenParen) => false,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dump::{astJson, Json};
+    use crate::lexer::lexCode;
+    use crate::parser::parseCode;
+
+    fn format(source: &str) -> String {
+        formatCode(source, &lexCode(source.to_string(), "test.bg").unwrap())
+    }
+
+    // The AST as JSON without any spans, since formatting moves things
+    fn shape(source: &str) -> Json {
+        fn strip(json: Json) -> Json {
+            match json {
+                Json::Array(items) => Json::Array(items.into_iter().map(strip).collect()),
+                Json::Object(fields) => Json::Object(
+                    fields.into_iter().filter(|(k, _)| k != "span").map(|(k, v)| (k, strip(v))).collect(),
+                ),
+                other => other,
+            }
+        }
+        strip(astJson(&parseCode(lexCode(source.to_string(), "test.bg").unwrap()).unwrap()))
+    }
+
+    const MESSY: &str = "
+/// A point
+struct Point {x int;   y int?;}
+enum Shape {Circle(int),Square(int,int),
+  Empty}
+
+
+var   p Point=Point{x=1,y=null};   // trailing
+var s Shape = Shape :: Square( 3 , 4 );
+/* own line */
+var area int=match(s){Shape::Circle(r)=>r*r*3,Shape::Square(w,h)=>w*h,_=>0};
+if(area>10){area=1;/* inline */ area->;}
+elif (area == 0) {
+
+  area = 2;
+
+}
+else{area=3;}
+for(var i int=0;i<3;i=i+1){area=area+i;}
+var q int = p?.y ?? 0;
+var r int = match (q) { 0..=9 => 1, _ => 2 };
+";
+
+    const CLEAN: &str = "/// A point
+struct Point {
+    x int;
+    y int?;
+}
+enum Shape {
+    Circle(int),
+    Square(int, int),
+    Empty
+}
+
+var p Point = Point { x = 1, y = null }; // trailing
+var s Shape = Shape::Square(3, 4);
+/* own line */
+var area int = match (s) { Shape::Circle(r) => r * r * 3, Shape::Square(w, h) => w * h, _ => 0 };
+if (area > 10) {
+    area = 1; /* inline */
+    area ->;
+} elif (area == 0) {
+    area = 2;
+} else {
+    area = 3;
+}
+for (var i int = 0; i < 3; i = i + 1) {
+    area = area + i;
+}
+var q int = p?.y ?? 0;
+var r int = match (q) { 0..=9 => 1, _ => 2 };
+";
+
+    #[test]
+    fn formatsToTheCanonicalStyle() {
+        assert_eq!(format(MESSY), CLEAN);
+    }
+
+    #[test]
+    fn formattingIsIdempotent() {
+        assert_eq!(format(CLEAN), CLEAN);
+        assert_eq!(format(&format(MESSY)), format(MESSY));
+    }
+
+    #[test]
+    fn formattingKeepsTheAst() {
+        assert_eq!(shape(&format(MESSY)), shape(MESSY));
+    }
+
+    #[test]
+    fn keepsEveryComment() {
+        let formatted = format(MESSY);
+        for comment in ["/// A point", "// trailing", "/* own line */", "/* inline */"] {
+            assert!(formatted.contains(comment), "lost `{}`", comment);
+        }
+    }
+}
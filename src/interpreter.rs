@@ -0,0 +1,392 @@
+use std::fmt;
+
+use crate::checker::checkTypesAfter;
+use crate::compiler::compile;
+use crate::evaluator::coerce_to_type;
+use crate::jadeErrors::{functionNameError, inferNullError, JadeError};
+use crate::lexer::{lexWithBuiltIns, openFile, resolveKeyword, Token};
+use crate::optimizer::{optimize, DEAD_BRANCHES};
+use crate::parser::{checkMatchesAfter, parseRecovering, Statement};
+use crate::resolver::resolve;
+use crate::runtime::{inferredType, runStatements, typeOf, Runtime, Value};
+use crate::vm::Vm;
+
+/// What runs the code. The tree-walking runtime is the reference, the
+/// VM has to give the same results.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Engine {
+    Tree,
+    Vm,
+}
+
+/// Everything that stopped a piece of code from running, with the code
+/// itself so each error can point at its line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalError {
+    pub errors: Vec<JadeError>,
+    pub source: String,
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rendered: Vec<String> = self.errors.iter().map(|error| error.render(&self.source)).collect();
+        write!(f, "{}", rendered.join("\n\n"))
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+/// Runs Jade code from Rust. Each `eval` carries on from the last one,
+/// so the globals, structs and enums it declared are still there.
+///
+/// ```
+/// use jade_lang::{Interpreter, Value};
+///
+/// let mut jade = Interpreter::new();
+/// jade.eval_str("var total int64 = 40;").unwrap();
+/// jade.set("total", Value::Int64(41)).unwrap();
+/// jade.eval_str("total = total + 1;").unwrap();
+/// assert_eq!(jade.get("total"), Some(&Value::Int64(42)));
+/// ```
+pub struct Interpreter {
+    runtime: Runtime,
+    engine: Engine,
+    optLevel: u8,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
+impl Interpreter {
+    /// Runs on the tree-walking runtime with every optimization.
+    pub fn new() -> Self {
+        Interpreter::with_options(Engine::Tree, DEAD_BRANCHES)
+    }
+
+    pub fn with_options(engine: Engine, optLevel: u8) -> Self {
+        Interpreter { runtime: Runtime::new(), engine, optLevel }
+    }
+
+    /// Runs a piece of Jade code. Nothing runs if it doesn't lex, parse
+    /// or type check, every error found is given back.
+    pub fn eval_str(&mut self, code: &str) -> Result<(), EvalError> {
+        self.eval(code, "<eval>")
+    }
+
+    /// Runs the Jade code in a file, errors point into that file.
+    pub fn eval_file(&mut self, fileName: &str) -> Result<(), EvalError> {
+        let code = openFile(fileName).map_err(|error| EvalError { errors: vec![error], source: String::new() })?;
+        self.eval(&code, fileName)
+    }
+
+    /// The value of a global, if it has been declared.
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.runtime.get_variable(name)
+    }
+
+    /// Sets a global. An existing one keeps its type and the value has
+    /// to fit it, a new one gets its type from the value like `var`.
+    pub fn set(&mut self, name: &str, value: Value) -> Result<(), JadeError> {
+        let current = self.runtime.slot_of(name).and_then(|slot| self.runtime.global(slot));
+        let datatype = match current {
+            Some((current, declared)) => typeOf(current, declared).clone(),
+            None => inferredType(&value).ok_or_else(|| inferNullError(name))?,
+        };
+        let value = coerce_to_type(value, &datatype)?;
+        let slot = self.runtime.slot_for(name);
+        self.runtime.declare_variable(slot, value, datatype);
+        Ok(())
+    }
+
+    /// Sends everything the script prints to `output` instead of stdout.
+    ///
+    /// ```
+    /// use jade_lang::{Capture, Interpreter};
+    ///
+    /// let output = Capture::new();
+    /// let mut jade = Interpreter::new();
+    /// jade.set_output(output.clone());
+    /// jade.eval_str("print(\"hi\");\nprint(1.0);").unwrap();
+    /// assert_eq!(output.contents(), "hi\n1.0\n");
+    /// ```
+    pub fn set_output(&mut self, output: impl std::io::Write + 'static) {
+        self.runtime.set_output(output);
+    }
+
+    /// Makes `name <<;` read its lines from `input` instead of stdin.
+    pub fn set_input(&mut self, input: impl std::io::BufRead + 'static) {
+        self.runtime.set_input(input);
+    }
+
+    /// Lets scripts call `body` as `name(...)`, like `print`. `params` is
+    /// the Jade type of each argument, calls with the wrong number of
+    /// arguments or ones that don't fit are errors before `body` runs.
+    ///
+    /// ```
+    /// use jade_lang::{Interpreter, JadeError, Value};
+    ///
+    /// let mut jade = Interpreter::new();
+    /// jade.register_fn("shout", &["string"], |args: &[Value]| -> Result<Value, JadeError> {
+    ///     match &args[0] {
+    ///         Value::String(text) => Ok(Value::String(text.to_uppercase())),
+    ///         _ => unreachable!("the argument was checked"),
+    ///     }
+    /// }).unwrap();
+    /// jade.eval_str("var loud = shout(\"hi\");").unwrap();
+    /// assert_eq!(jade.get("loud"), Some(&Value::String("HI".into())));
+    /// ```
+    pub fn register_fn(
+        &mut self,
+        name: &str,
+        params: &[&str],
+        body: impl Fn(&[Value]) -> Result<Value, JadeError> + 'static,
+    ) -> Result<(), JadeError> {
+        // It has to lex as one name that isn't already a keyword
+        let isName = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !isName || !matches!(resolveKeyword(name), Token::Name | Token::BuiltIn) {
+            return Err(functionNameError(name));
+        }
+        self.runtime.register_fn(name, params, body);
+        Ok(())
+    }
+
+    fn eval(&mut self, code: &str, fileName: &str) -> Result<(), EvalError> {
+        self.run(code, fileName).map_err(|mut errors| {
+            errors.sort_by_key(|error| error.diagnostic().span.as_ref().map(|span| span.start));
+            EvalError { errors, source: code.to_string() }
+        })
+    }
+
+    fn run(&mut self, code: &str, fileName: &str) -> Result<(), Vec<JadeError>> {
+        // Bad characters don't stop us yet, the parser still gets a look
+        // so every mistake in the code is reported in one go
+        let functions: Vec<String> = self.runtime.signatures().into_keys().collect();
+        let (tokens, mut errors) = lexWithBuiltIns(code.to_string(), fileName, &functions);
+        let (parsedCode, parseErrors): (Vec<Statement>, Vec<JadeError>) = parseRecovering(tokens);
+        errors.extend(parseErrors);
+
+        // Check the types, unless a statement didn't parse. Anything it
+        // declared would show up as undeclared further down.
+        if errors.is_empty() && let Err(typeErrors) = checkTypesAfter(&parsedCode, &self.runtime) {
+            errors.extend(typeErrors);
+        }
+
+        // Make sure every match covers all of its cases
+        if let Err(matchErrors) = checkMatchesAfter(&parsedCode, &self.runtime.enums) {
+            errors.extend(matchErrors);
+        }
+
+        // Nothing runs if anything above went wrong
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        // Fold what can be worked out now, the checks above saw the code
+        // as it was written
+        let mut parsedCode: Vec<Statement> = optimize(parsedCode, self.optLevel);
+
+        match self.engine {
+            Engine::Tree => {
+                resolve(&mut parsedCode, &mut self.runtime.names)?;
+                runStatements(&parsedCode, &mut self.runtime)?;
+            }
+            Engine::Vm => {
+                let chunk = compile(&parsedCode);
+                let mut vm = Vm::with_runtime(&chunk, std::mem::take(&mut self.runtime));
+                let result = vm.run();
+                self.runtime = vm.into_runtime();
+                result?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Capture;
+    use std::collections::HashMap;
+
+    #[test]
+    fn globalsCarryOverBetweenEvals() {
+        for engine in [Engine::Tree, Engine::Vm] {
+            let mut jade = Interpreter::with_options(engine, DEAD_BRANCHES);
+            jade.eval_str("struct Point { x int; y int; }\nvar p = Point { x = 1, y = 2 };\nvar n int32 = 5;").unwrap();
+            jade.eval_str("p.x = p.y + n;\nn = n * 2;").unwrap();
+            assert_eq!(jade.get("n"), Some(&Value::Int32(10)), "{:?}", engine);
+            let point = Value::Struct("Point".into(), vec![("x".into(), Value::Int8(7)), ("y".into(), Value::Int8(2))]);
+            assert_eq!(jade.get("p"), Some(&point), "{:?}", engine);
+        }
+    }
+
+    #[test]
+    fn enumsCarryOverBetweenEvals() {
+        for engine in [Engine::Tree, Engine::Vm] {
+            let mut jade = Interpreter::with_options(engine, DEAD_BRANCHES);
+            jade.eval_str("enum Color { Red, Green }\nvar c = Color::Green;").unwrap();
+            jade.eval_str("var n int32 = match (c) { Color::Red => 1, Color::Green => 2 };").unwrap();
+            assert_eq!(jade.get("n"), Some(&Value::Int32(2)), "{:?}", engine);
+            let error = jade.eval_str("var m int32 = match (c) { Color::Red => 1 };").unwrap_err();
+            assert!(error.errors[0].diagnostic().message.contains("Color::Green"), "{:?}", engine);
+        }
+    }
+
+    #[test]
+    fn setKeepsTheDeclaredType() {
+        let mut jade = Interpreter::new();
+        jade.eval_str("var small int8 = 1;").unwrap();
+        jade.set("small", Value::Int64(100)).unwrap();
+        assert_eq!(jade.get("small"), Some(&Value::Int8(100)));
+        assert!(jade.set("small", Value::Int64(1000)).is_err());
+        assert!(jade.set("small", Value::Bool(true)).is_err());
+
+        // New globals can be used straight away
+        jade.set("limit", Value::Int64(3)).unwrap();
+        jade.eval_str("var over = small > limit;").unwrap();
+        assert_eq!(jade.get("over"), Some(&Value::Bool(true)));
+        assert!(jade.set("nothing", Value::Null).is_err());
+    }
+
+    // `clamp(value, low, high)` for int32s
+    fn withClamp(engine: Engine) -> Interpreter {
+        let mut jade = Interpreter::with_options(engine, DEAD_BRANCHES);
+        jade.register_fn("clamp", &["int32", "int32", "int32"], |args| match args {
+            [Value::Int32(value), Value::Int32(low), Value::Int32(high)] => Ok(Value::Int32(*value.max(low).min(high))),
+            _ => Err(JadeError::Type(String::from("clamp takes int32s").into())),
+        })
+        .unwrap();
+        jade
+    }
+
+    #[test]
+    fn registeredFunctionsAreCallable() {
+        for engine in [Engine::Tree, Engine::Vm] {
+            let mut jade = withClamp(engine);
+            jade.eval_str("var a int32 = 50;\nvar b int32 = clamp(a * 3, 0, 100);\nclamp(1, 2, 3);").unwrap();
+            assert_eq!(jade.get("b"), Some(&Value::Int32(100)), "{:?}", engine);
+        }
+    }
+
+    #[test]
+    fn callsAreCheckedBeforeRunning() {
+        let mut jade = withClamp(Engine::Tree);
+        let error = jade.eval_str("var a = clamp(1, 2);\nvar b = clamp(true, 1, 2);\nvar c = nope(1);").unwrap_err();
+        let messages: Vec<&str> = error.errors.iter().map(|e| e.diagnostic().message.as_str()).collect();
+        assert_eq!(messages[0], "clamp takes 3 argument(s), found 2");
+        assert_eq!(messages[1], "Type mismatch! Expected: int32, Found: bool");
+        assert_eq!(messages.len(), 3);
+
+        // The runtime checks them too, for values only known when it runs
+        jade.set("big", Value::Int64(1 << 40)).unwrap();
+        let error = jade.eval_str("var c = clamp(big, 0, 1);").unwrap_err();
+        assert!(matches!(error.errors[0], JadeError::Type(_)));
+        assert_eq!(jade.get("c"), None);
+    }
+
+    #[test]
+    fn functionNamesHaveToBeNames() {
+        let mut jade = Interpreter::new();
+        for name in ["if", "var", "2fast", "", "a-b"] {
+            assert!(jade.register_fn(name, &[], |_| Ok(Value::Null)).is_err(), "`{}`", name);
+        }
+        assert!(jade.register_fn("print", &["$"], |_| Ok(Value::Null)).is_ok());
+    }
+
+    #[test]
+    fn printsGoToTheOutput() {
+        let code = "struct Point { x int; y int; }
+            enum Shape { Dot, Circle(float) }
+            var p = Point { x = 1, y = 2 };
+            var s = Shape::Circle(2.5);
+            var name = \"jade\";
+            print(p);
+            print(s);
+            print(Shape::Dot);
+            name ->;
+            print(1.0 + 2.0);
+            print(null);";
+        for engine in [Engine::Tree, Engine::Vm] {
+            let output = Capture::new();
+            let mut jade = Interpreter::with_options(engine, DEAD_BRANCHES);
+            jade.set_output(output.clone());
+            jade.eval_str(code).unwrap();
+            assert_eq!(
+                output.contents(),
+                "Point { x: 1, y: 2 }\nShape::Circle(2.5)\nShape::Dot\njade\n3.0\nnull\n",
+                "{:?}",
+                engine
+            );
+        }
+    }
+
+    #[test]
+    fn printsCanGoToAFile() {
+        let file = std::env::temp_dir().join(format!("jade-print-{}.txt", std::process::id()));
+        let fileName = file.to_str().unwrap();
+        for engine in [Engine::Tree, Engine::Vm] {
+            let output = Capture::new();
+            let mut jade = Interpreter::with_options(engine, DEAD_BRANCHES);
+            jade.set_output(output.clone());
+            jade.set("file", Value::from(fileName)).unwrap();
+            jade.eval_str("var a = 2;\n(a + 1) * 2 ->;\na, \"and\", a > 1 ->;\n\"first\" -> file;\n\"total\", a -> file;").unwrap();
+            assert_eq!(output.contents(), "6\n2 and true\n", "{:?}", engine);
+            assert_eq!(std::fs::read_to_string(&file).unwrap(), "total 2\n", "{:?}", engine);
+        }
+        std::fs::remove_file(file).unwrap();
+        assert!(Interpreter::new().eval_str("1 -> 2;").is_err());
+    }
+
+    #[test]
+    fn appendsAndReads() {
+        let log = std::env::temp_dir().join(format!("jade-append-{}.log", std::process::id()));
+        for engine in [Engine::Tree, Engine::Vm] {
+            let mut jade = Interpreter::with_options(engine, DEAD_BRANCHES);
+            jade.set_input(std::io::Cursor::new("first\r\nsecond"));
+            jade.set("items", Value::from(vec![1i64])).unwrap();
+            jade.set("path", Value::from(log.to_str().unwrap())).unwrap();
+            jade.eval_str(&format!("sink log = path;\nvar text = \"a\";\n\"b\" >> text;\n2 >> items;\ntext >> log;\n3 >> {:?};", log)).unwrap();
+            assert_eq!(jade.get("text"), Some(&Value::from("ab")), "{:?}", engine);
+            assert_eq!(jade.get("items"), Some(&Value::from(vec![1i64, 2])), "{:?}", engine);
+
+            jade.eval_str("var a string? = \"\";\nvar b string? = \"\";\nvar c string? = \"\";\na <<;\nb <<;\nc <<;\ntext << log;").unwrap();
+            assert_eq!(jade.get("a"), Some(&Value::from("first")), "{:?}", engine);
+            assert_eq!(jade.get("b"), Some(&Value::from("second")), "{:?}", engine);
+            assert_eq!(jade.get("c"), Some(&Value::Null), "{:?}", engine);
+            assert_eq!(jade.get("text"), Some(&Value::from("ab\n3\n")), "{:?}", engine);
+            std::fs::remove_file(&log).unwrap();
+        }
+
+        let mut jade = Interpreter::new();
+        let error = jade.eval_str("var n = 1;\n2 >> n;\nn << \"in.txt\";").unwrap_err();
+        assert_eq!(error.errors[0].diagnostic().message, "Can't append to int, only to arrays, strings and sinks");
+        assert_eq!(error.errors.len(), 2);
+    }
+
+    #[test]
+    fn stringsAreQuotedInsideOtherValues() {
+        let words = Value::Array(vec![Value::from("a b"), Value::Int8(1), Value::Float32(0.1)]);
+        assert_eq!(words.to_string(), "[\"a b\", 1, 0.1]");
+        let map = Value::Map(HashMap::from([("b".into(), Value::Bool(true)), ("a".into(), Value::from("x"))]));
+        assert_eq!(map.to_string(), "{a: \"x\", b: true}");
+        assert_eq!(Value::Float64(1e21).to_string(), "1e21");
+    }
+
+    #[test]
+    fn errorsComeBackWithTheirSpans() {
+        let mut jade = Interpreter::new();
+        let error = jade.eval_str("var a int = \"x\";\nvar b = missing;").unwrap_err();
+        assert_eq!(error.errors.len(), 2);
+        assert_eq!(error.errors[1].diagnostic().span.as_ref().map(|span| span.line), Some(2));
+        assert!(error.to_string().contains("2 | var b = missing;"));
+
+        // Nothing ran, and the interpreter can still be used
+        assert_eq!(jade.get("a"), None);
+        jade.eval_str("var a = 1;").unwrap();
+        assert!(matches!(jade.eval_file("no/such/file.bg").unwrap_err().errors[0], JadeError::IO(_)));
+    }
+}
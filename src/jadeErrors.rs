@@ -1,3 +1,6 @@
+use std::fmt;
+use crate::lexer::Span;
+use crate::parser::BinaryOperator;
 use crate::runtime::Value;
 
 /*
@@ -12,15 +15,336 @@ pub fn noEndingSemiColon(line:&str) {
 
 */
 
-pub fn parsingError() {
-    eprintln!("WTF is this dude!!? You never told me this was an option!?");
+/// The details of an error: what went wrong, where, and maybe a hint
+/// on how to fix it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Option<Span>,
+    pub hint: Option<String>,
 }
 
-pub fn valueError(type_str: &str, other: Value) {
-    println!("\n\n____________________________________________\nVALUE ERROR:");
-    panic!("Error! Type mismatch! Expected: {type_str}, Found: {:?}\n____________________________________________", other);
+impl From<String> for Diagnostic {
+    fn from(message: String) -> Self {
+        Diagnostic { message, span: None, hint: None }
+    }
 }
 
-pub fn variableNotFoundError(name: &str) {
-    eprintln!("Variable '{}' not found", name);
-}
\ No newline at end of file
+/// Every error Jade can run into. Nothing panics anymore, the error is
+/// handed back up so the CLI (or whoever is running the code) can
+/// report it and stop.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JadeError {
+    Lex(Diagnostic),        // Characters the lexer doesn't know
+    Parse(Diagnostic),      // Tokens in an order the parser doesn't know
+    Type(Diagnostic),       // Values that don't fit where they're used
+    Name(Diagnostic),       // Variables, types or fields that don't exist
+    Arithmetic(Diagnostic), // Overflow and dividing by zero
+    IO(Diagnostic),         // Files that can't be opened or read
+}
+
+impl JadeError {
+    pub fn diagnostic(&self) -> &Diagnostic {
+        match self {
+            JadeError::Lex(d) | JadeError::Parse(d) | JadeError::Type(d) |
+            JadeError::Name(d) | JadeError::Arithmetic(d) | JadeError::IO(d) => d,
+        }
+    }
+
+    fn diagnosticMut(&mut self) -> &mut Diagnostic {
+        match self {
+            JadeError::Lex(d) | JadeError::Parse(d) | JadeError::Type(d) |
+            JadeError::Name(d) | JadeError::Arithmetic(d) | JadeError::IO(d) => d,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            JadeError::Lex(_)        => "LEX ERROR",
+            JadeError::Parse(_)      => "PARSE ERROR",
+            JadeError::Type(_)       => "TYPE ERROR",
+            JadeError::Name(_)       => "NAME ERROR",
+            JadeError::Arithmetic(_) => "ARITHMETIC ERROR",
+            JadeError::IO(_)         => "IO ERROR",
+        }
+    }
+
+    /// Points the error at `span`, unless something deeper already did.
+    pub fn at(mut self, span: Span) -> Self {
+        let diagnostic = self.diagnosticMut();
+        if diagnostic.span.is_none() {
+            diagnostic.span = Some(span);
+        }
+        self
+    }
+
+    /// Adds a hint that gets printed under the error.
+    pub fn withHint(mut self, hint: String) -> Self {
+        self.diagnosticMut().hint = Some(hint);
+        self
+    }
+
+    /// The error with the line of `source` it points at, underlined:
+    ///
+    /// ```text
+    /// NAME ERROR: Variable 'myVr' not found
+    ///   --> main.bg:2:13
+    ///   |
+    /// 2 | var y int = myVr + 1;
+    ///   |             ^~~~
+    ///   = hint: did you mean `myVar`?
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        let diagnostic = self.diagnostic();
+        let mut out = format!("{}: {}", self.label(), diagnostic.message);
+
+        if let Some(span) = &diagnostic.span {
+            let lineText = source.lines().nth(span.line.saturating_sub(1)).unwrap_or("");
+            let gutter = " ".repeat(span.line.to_string().len());
+
+            // Only underline the part of the span on this line
+            let lineEnd = lineText.chars().count() + 1;
+            let width = source
+                .get(span.start..span.end)
+                .map(|text| text.lines().next().unwrap_or("").chars().count())
+                .unwrap_or(1)
+                .clamp(1, lineEnd.saturating_sub(span.column).max(1));
+
+            out.push_str(&format!("\n{} --> {}:{}:{}", gutter, span.file, span.line, span.column));
+            out.push_str(&format!("\n{} |", gutter));
+            out.push_str(&format!("\n{} | {}", span.line, lineText));
+            out.push_str(&format!(
+                "\n{} | {}^{}",
+                gutter,
+                " ".repeat(span.column.saturating_sub(1)),
+                "~".repeat(width - 1)
+            ));
+            if let Some(hint) = &diagnostic.hint {
+                out.push_str(&format!("\n{} = hint: {}", gutter, hint));
+            }
+        } else if let Some(hint) = &diagnostic.hint {
+            out.push_str(&format!("\n  = hint: {}", hint));
+        }
+
+        out
+    }
+}
+
+impl fmt::Display for JadeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let diagnostic = self.diagnostic();
+        write!(f, "{}: {}", self.label(), diagnostic.message)?;
+        if let Some(span) = &diagnostic.span {
+            write!(f, " at {}:{}:{}", span.file, span.line, span.column)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for JadeError {}
+
+// Lets a single error be `?`-ed out of functions that report many
+impl From<JadeError> for Vec<JadeError> {
+    fn from(error: JadeError) -> Self {
+        vec![error]
+    }
+}
+
+/// The candidate closest to `name`, if any is close enough to be a typo.
+pub fn closestName<'a>(name: &str, candidates: impl Iterator<Item = &'a String>) -> Option<&'a String> {
+    let limit = (name.chars().count() / 3).max(1);
+    candidates
+        .map(|candidate| (editDistance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+// Levenshtein distance between two names
+fn editDistance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = previous[j] + usize::from(ca != *cb);
+            current.push(substitute.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Adds a "did you mean" hint when one of the candidates looks like a
+/// typo of `name`.
+pub fn suggest<'a>(error: JadeError, name: &str, candidates: impl Iterator<Item = &'a String>) -> JadeError {
+    match closestName(name, candidates) {
+        Some(candidate) => error.withHint(format!("did you mean `{}`?", candidate)),
+        None => error,
+    }
+}
+
+pub fn literalRangeError(literal: &str, type_str: &str) -> JadeError {
+    JadeError::Type(format!("`{}` doesn't fit in {}", literal, type_str).into())
+}
+
+pub fn valueError(type_str: &str, other: Value) -> JadeError {
+    JadeError::Type(format!("Type mismatch! Expected: {type_str}, Found: {}", other).into())
+}
+
+pub fn typeMismatchError(expected: &str, found: &str) -> JadeError {
+    JadeError::Type(format!("Type mismatch! Expected: {}, Found: {}", expected, found).into())
+}
+
+pub fn inferNullError(name: &str) -> JadeError {
+    JadeError::Type(format!("Can't tell the type of '{}' from null", name).into())
+        .withHint(format!("give it a type, like `var {} int? = null;`", name))
+}
+
+pub fn typeNotFoundError(name: &str) -> JadeError {
+    JadeError::Name(format!("Type '{}' not found", name).into())
+}
+
+pub fn variableNotFoundError(name: &str) -> JadeError {
+    JadeError::Name(format!("Variable '{}' not found", name).into())
+}
+
+pub fn functionNotFoundError(name: &str) -> JadeError {
+    JadeError::Name(format!("Function '{}' not found", name).into())
+}
+
+pub fn functionNameError(name: &str) -> JadeError {
+    JadeError::Name(format!("'{}' can't be used as a function name", name).into())
+}
+
+pub fn argumentCountError(name: &str, expected: usize, found: usize) -> JadeError {
+    JadeError::Type(format!("{} takes {} argument(s), found {}", name, expected, found).into())
+}
+
+pub fn structNotFoundError(name: &str) -> JadeError {
+    JadeError::Name(format!("Struct '{}' not found", name).into())
+}
+
+pub fn fieldNotFoundError(structName: &str, field: &str) -> JadeError {
+    JadeError::Name(format!("Struct '{}' has no field '{}'", structName, field).into())
+}
+
+pub fn missingFieldError(structName: &str, field: &str) -> JadeError {
+    JadeError::Type(format!("Struct '{}' is missing field '{}'", structName, field).into())
+}
+
+pub fn fieldAccessError(field: &str, typeName: &str) -> JadeError {
+    JadeError::Type(format!("Can't access field '{}' on {}", field, typeName).into())
+}
+
+pub fn enumNotFoundError(name: &str) -> JadeError {
+    JadeError::Name(format!("Enum '{}' not found", name).into())
+}
+
+pub fn variantNotFoundError(enumName: &str, variant: &str) -> JadeError {
+    JadeError::Name(format!("Enum '{}' has no variant '{}'", enumName, variant).into())
+}
+
+pub fn payloadCountError(enumName: &str, variant: &str, expected: usize, found: usize) -> JadeError {
+    JadeError::Type(format!("{}::{} takes {} value(s), found {}", enumName, variant, expected, found).into())
+}
+
+pub fn nonExhaustiveMatchError(missing: &str) -> JadeError {
+    JadeError::Type(format!("Match is not exhaustive, missing: {}", missing).into())
+}
+
+pub fn noMatchingArmError(value: &Value) -> JadeError {
+    JadeError::Type(format!("No match arm for {}", value).into())
+}
+
+pub fn nullOperandError(op: &BinaryOperator) -> JadeError {
+    JadeError::Type(format!("Can't use null with {:?}", op).into())
+}
+
+pub fn unsupportedOperationError(op: &BinaryOperator, l: &Value, r: &Value) -> JadeError {
+    JadeError::Type(format!("{:?} not supported between given types: {} and {}", op, l, r).into())
+}
+
+pub fn operandTypesError(op: &BinaryOperator, l: &str, r: &str) -> JadeError {
+    JadeError::Type(format!("{:?} not supported between given types: {} and {}", op, l, r).into())
+}
+
+pub fn unsupportedOperatorError(op: &BinaryOperator) -> JadeError {
+    JadeError::Type(format!("Unsupported binary operator: {:?}", op).into())
+}
+
+pub fn overflowError(op: &BinaryOperator, l: &Value, r: &Value) -> JadeError {
+    JadeError::Arithmetic(format!("{:?} overflowed with {} and {}", op, l, r).into())
+}
+
+pub fn divisionByZeroError(l: &Value) -> JadeError {
+    JadeError::Arithmetic(format!("Can't divide {} by zero", l).into())
+}
+
+pub fn writeFileError(fileName: &str, error: std::io::Error) -> JadeError {
+    JadeError::IO(format!("Failed to write '{}': {}", fileName, error).into())
+}
+
+pub fn appendError(found: &str) -> JadeError {
+    JadeError::Type(format!("Can't append to {}, only to arrays, strings and sinks", found).into())
+}
+
+pub fn inputError(error: std::io::Error) -> JadeError {
+    JadeError::IO(format!("Failed to read input: {}", error).into())
+}
+
+pub fn outputError(error: std::io::Error) -> JadeError {
+    JadeError::IO(format!("Failed to write output: {}", error).into())
+}
+
+pub fn fileError(fileName: &str, error: std::io::Error) -> JadeError {
+    JadeError::IO(format!("Failed to read '{}': {}", fileName, error).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(line: usize, column: usize, start: usize, end: usize) -> Span {
+        Span { file: "main.bg".to_string(), line, column, start, end }
+    }
+
+    #[test]
+    fn rendersTheLineAndUnderlinesTheSpan() {
+        let source = "var a int8 = 1;\nvar b int8 = a + 300;\n";
+        let error = overflowError(&BinaryOperator::Add, &Value::Int8(1), &Value::Int64(300)).at(span(2, 14, 29, 36));
+        assert_eq!(
+            error.render(source),
+            [
+                "ARITHMETIC ERROR: Add overflowed with 1 and 300",
+                "  --> main.bg:2:14",
+                "  |",
+                "2 | var b int8 = a + 300;",
+                "  |              ^~~~~~~",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn rendersADidYouMeanHint() {
+        let names = ["count".to_string(), "total".to_string()];
+        let source = "var count = 1;\ncont ->;\n";
+        let error = suggest(variableNotFoundError("cont").at(span(2, 1, 15, 19)), "cont", names.iter());
+        assert_eq!(
+            error.render(source),
+            [
+                "NAME ERROR: Variable 'cont' not found",
+                "  --> main.bg:2:1",
+                "  |",
+                "2 | cont ->;",
+                "  | ^~~~",
+                "  = hint: did you mean `count`?",
+            ]
+            .join("\n")
+        );
+
+        let error = suggest(variableNotFoundError("zebra"), "zebra", names.iter());
+        assert_eq!(error.render(""), "NAME ERROR: Variable 'zebra' not found");
+    }
+}
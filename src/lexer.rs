@@ -3,24 +3,35 @@ extern crate regex;
 //use regex::Regex;
 use std::fs::File;
 use std::io::Read;
-use logos::Logos;
+use logos::{FilterResult, Lexer, Logos};
+use crate::jadeErrors::{fileError, JadeError};
 
 /// The tokens to be used with the parser.
 #[allow(dead_code)]
 #[derive(Logos, Debug, PartialEq, Clone)]
 #[logos(skip r"[ \t\n\f\r]+")] // Ignore this regex pattern between tokens
 pub enum Token {
-    // COMMENT
-    #[regex(r"//[^\n]*", logos::skip)]
-    Comment,
-    
+    // COMMENTS
+    // Comments are kept as tokens so the formatter can put them back.
+    // The parser treats them as trivia and skips them.
+    #[regex(r"//[^\n]*")]
+    LineComment,
+    // `/* ... */`, these can nest so they're matched by hand
+    #[token("/*", blockComment)]
+    BlockComment,
+    // `/// ...`, kept so they can be attached to the next declaration
+    #[regex(r"///[^\n]*", priority = 5)]
+    DocComment,
+
     // KEYWORDS
-    #[token("var")]
+    // These are never matched directly, words lex as `Name` and then
+    // get turned into keywords by `resolveKeyword`. See RESERVED_WORDS.
     Var,
-    #[token("const")]
     Const,
-    #[token("sink")]
     Sink,
+    Struct,
+    Enum,
+    Null,
 
     // CONTROL SYMBOLS
     #[token("=")]
@@ -35,6 +46,24 @@ pub enum Token {
     OpenParen,
     #[token(")")]
     CloseParen,
+    #[token(",")]
+    Comma,
+    #[token(".")]
+    Dot,
+    #[token("::")]
+    DoubleColon,
+    #[token("=>")]
+    FatArrow,
+    #[token("..")]
+    Range,
+    #[token("..=")]
+    RangeInclusive,
+    #[token("?")]
+    Question,
+    #[token("?.")]
+    OptionalDot,
+    #[token("??")]
+    NullCoalesce,
 
     // OPERATORS:
     // Math
@@ -68,22 +97,15 @@ pub enum Token {
     LessOEqual,
     #[token("%")]
     Modulo,
-    #[token("AND")]
     #[token("&&")]
     And,
-    #[regex("OR")]
     #[token("||")]
     Or,
-    #[regex("NOT")]
     #[token("!")]
     Not,
-    #[token("XOR")]
     Xor,
-    #[token("NAND")]
     Nand,
-    #[token("NOR")]
     Nor,
-    #[token("XNOR")]
     Xnor,
 
     //MISCSymbols
@@ -95,52 +117,363 @@ pub enum Token {
     Print,
 
     // IDENTIFIERS
-    #[regex(r"[a-zA-Z_][a-zA-Z0-9_]*", priority = 1)]
+    // Every word, keywords included, see `resolveKeyword`
+    #[regex(r"[a-zA-Z_][a-zA-Z0-9_]*")]
     Name,
 
     // KEYWORDS
-    #[regex(r"if|elif|else|while|unless|for|until|case")]
     ControlBlock,
-    #[regex(r"print")]
     BuiltIn,
-    #[regex(r"\$|@|%|")]
+    #[token("$")]
+    #[token("@")]
     DataType,
 
     // LITERALS (simplified)
-    #[regex(r"\d+\.\d+|\d+", priority = 3)]
+    // `0xFF`, `0b1010`, `0o17`, `1_000`, `1.5e-3`, and any of them can
+    // end in a width like `u8` or `f32`. See parser::parseNumber.
+    #[regex(r"0[xX][0-9a-fA-F_]+([ui](8|16|32|64))?")]
+    #[regex(r"0[bB][01_]+([ui](8|16|32|64))?")]
+    #[regex(r"0[oO][0-7_]+([ui](8|16|32|64))?")]
+    #[regex(r"\d[\d_]*(\.\d[\d_]*)?([eE][+-]?\d+)?(u8|u16|u32|u64|i8|i16|i32|i64|f32|f64)?")]
     Number,
     #[regex(r#"["'][^"']*["']"#)]
     String,
-    #[regex(r"[Tt][Rr][Uu][Ee]|[Ff][Aa][Ll][Ss][Ee]|1|0", priority = 2)]
     Bool,
 }
 
-pub fn openFile(fileName: &str) -> String {
+/// Matches a `/* ... */` comment, counting any `/*` inside it so nested
+/// comments close at the right `*/`.
+fn blockComment(lex: &mut Lexer<Token>) -> FilterResult<(), ()> {
+    let rest = lex.remainder();
+    let mut depth: usize = 1;
+    let mut idx: usize = 0;
+    while idx + 1 < rest.len() {
+        match &rest.as_bytes()[idx..idx + 2] {
+            b"/*" => {
+                depth += 1;
+                idx += 2;
+            }
+            b"*/" => {
+                depth -= 1;
+                idx += 2;
+                if depth == 0 {
+                    lex.bump(idx);
+                    return FilterResult::Emit(());
+                }
+            }
+            _ => idx += 1,
+        }
+    }
+
+    // Never closed, the rest of the file is the comment
+    lex.bump(rest.len());
+    FilterResult::Error(())
+}
+
+/// The reserved words and the token each one lexes as. Words can't be
+/// used as names if they're in here. Everything is case sensitive
+/// except `true` and `false`, which match in any case.
+///
+/// | Words                                                        | Token          |
+/// |--------------------------------------------------------------|----------------|
+/// | `var`, `const`, `sink`                                       | `Var`, `Const`, `Sink` |
+/// | `struct`, `enum`                                             | `Struct`, `Enum` |
+/// | `null`                                                       | `Null`         |
+/// | `if`, `elif`, `else`, `while`, `unless`, `for`, `until`, `case`, `match` | `ControlBlock` |
+/// | `print`                                                      | `BuiltIn`      |
+/// | `true`, `false`                                              | `Bool`         |
+/// | `AND`, `OR`, `NOT`, `XOR`, `NAND`, `NOR`, `XNOR`             | the operator   |
+pub const RESERVED_WORDS: &[(&str, Token)] = &[
+    ("var", Token::Var),
+    ("const", Token::Const),
+    ("sink", Token::Sink),
+    ("struct", Token::Struct),
+    ("enum", Token::Enum),
+    ("null", Token::Null),
+    ("if", Token::ControlBlock),
+    ("elif", Token::ControlBlock),
+    ("else", Token::ControlBlock),
+    ("while", Token::ControlBlock),
+    ("unless", Token::ControlBlock),
+    ("for", Token::ControlBlock),
+    ("until", Token::ControlBlock),
+    ("case", Token::ControlBlock),
+    ("match", Token::ControlBlock),
+    ("print", Token::BuiltIn),
+    ("true", Token::Bool),
+    ("false", Token::Bool),
+    ("AND", Token::And),
+    ("OR", Token::Or),
+    ("NOT", Token::Not),
+    ("XOR", Token::Xor),
+    ("NAND", Token::Nand),
+    ("NOR", Token::Nor),
+    ("XNOR", Token::Xnor),
+];
+
+/// The keyword-resolution stage: a whole word is a keyword only if it's
+/// in RESERVED_WORDS, so `iffy` stays a `Name` and `if` doesn't.
+pub fn resolveKeyword(word: &str) -> Token {
+    let lowered = word.to_lowercase();
+    RESERVED_WORDS
+        .iter()
+        .find(|(reserved, token)| {
+            *reserved == word || (*token == Token::Bool && *reserved == lowered)
+        })
+        .map(|(_, token)| token.clone())
+        .unwrap_or(Token::Name)
+}
+
+pub fn openFile(fileName: &str) -> Result<String, JadeError> {
     // Try to open the file
-    let mut file: File = File::open(fileName).expect("Failed to open file");
+    let mut file: File = File::open(fileName).map_err(|e| fileError(fileName, e))?;
 
     // Make a string to hold the contents
     let mut contents: String = String::new();
 
     // Read the file into the string
-    file.read_to_string(&mut contents).expect("Failed to read file");
+    file.read_to_string(&mut contents).map_err(|e| fileError(fileName, e))?;
 
     // Return the string containing the code from the Jade File
-    return contents;
+    Ok(contents)
+}
+
+/// Where something came from in the source. Lines and columns start
+/// at 1, `start` and `end` are byte offsets into the source.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Span {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub start: usize,
+    pub end: usize,
 }
 
-/// turns the raw code into a list of token:value pairs.
+impl Span {
+    /// A span running from the start of this one to the end of `other`.
+    pub fn to(&self, other: &Span) -> Span {
+        Span { end: other.end.max(self.end), ..self.clone() }
+    }
+}
+
+/// turns the raw code into a list of token:value:span triples.
 /// Used after openFile()
-pub fn lexCode(rawCode: String) -> Vec<(Token, String)> {
-    let mut tokens: Vec<(Token, String)> = Vec::new();
+///
+/// Characters the lexer doesn't know don't stop it, every run of them
+/// is reported and lexing carries on so all of them show up at once.
+pub fn lexCode(rawCode: String, fileName: &str) -> Result<Vec<(Token, String, Span)>, Vec<JadeError>> {
+    let (tokens, errors) = lexRecovering(rawCode, fileName);
+    if errors.is_empty() {
+        Ok(tokens)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Like lexCode, but hands back the tokens it did recognize alongside
+/// the errors so the parser can still look for mistakes of its own.
+pub fn lexRecovering(rawCode: String, fileName: &str) -> (Vec<(Token, String, Span)>, Vec<JadeError>) {
+    lexWithBuiltIns(rawCode, fileName, &[])
+}
+
+/// Like lexRecovering, with every name in `builtIns` lexing as a
+/// `BuiltIn` too. These are the functions registered from Rust.
+pub fn lexWithBuiltIns(rawCode: String, fileName: &str, builtIns: &[String]) -> (Vec<(Token, String, Span)>, Vec<JadeError>) {
+    let mut tokens: Vec<(Token, String, Span)> = Vec::new();
+    let mut errors: Vec<JadeError> = Vec::new();
+
+    // The run of unrecognized characters we're in the middle of, if any
+    let mut unknown: Option<Span> = None;
 
     let mut lexer = Token::lexer(rawCode.as_str());
 
+    // Track the line we're on so each span knows its line and column
+    let mut line: usize = 1;
+    let mut lineStart: usize = 0;
+    let mut scanned: usize = 0;
+
     while let Some(token) = lexer.next() {
+        let range = lexer.span();
+        for (idx, c) in rawCode[scanned..range.start].char_indices() {
+            if c == '\n' {
+                line += 1;
+                lineStart = scanned + idx + 1;
+            }
+        }
+        scanned = range.start;
+
+        let span = Span {
+            file: fileName.to_string(),
+            line,
+            column: rawCode[lineStart..range.start].chars().count() + 1,
+            start: range.start,
+            end: range.end,
+        };
+
         // `lexer.slice()` returns the actual text that matched the token
         //println!("Token: {:?} => Value: {:?}", token, lexer.slice());
-        tokens.push((token.unwrap(), lexer.slice().to_string()));
+        match token {
+            Ok(token) => {
+                if let Some(bad) = unknown.take() {
+                    errors.push(unrecognizedError(&rawCode, bad));
+                }
+                let token = match token {
+                    Token::Name => match resolveKeyword(lexer.slice()) {
+                        Token::Name if builtIns.iter().any(|name| name == lexer.slice()) => Token::BuiltIn,
+                        keyword => keyword,
+                    },
+                    other => other,
+                };
+                tokens.push((token, lexer.slice().to_string(), span));
+            }
+
+            // A `/*` that never gets its `*/`
+            Err(()) if lexer.slice().starts_with("/*") => {
+                errors.push(JadeError::Lex(String::from("Unterminated block comment").into())
+                    .at(Span { end: span.start + 2, ..span })
+                    .withHint(String::from("add a `*/` to close it")));
+            }
+
+            // Touching bad characters are reported together
+            Err(()) => {
+                unknown = match unknown.take() {
+                    Some(bad) if bad.end == span.start => Some(bad.to(&span)),
+                    Some(bad) => {
+                        errors.push(unrecognizedError(&rawCode, bad));
+                        Some(span)
+                    }
+                    None => Some(span),
+                };
+            }
+        }
+    }
+
+    if let Some(bad) = unknown {
+        errors.push(unrecognizedError(&rawCode, bad));
+    }
+
+    (tokens, errors)
+}
+
+fn unrecognizedError(rawCode: &str, span: Span) -> JadeError {
+    let text = &rawCode[span.start..span.end];
+    let message = if text.chars().count() == 1 {
+        format!("Unrecognized character `{}`", text)
+    } else {
+        format!("Unrecognized characters `{}`", text)
+    };
+    JadeError::Lex(message.into()).at(span)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lexOne(code: &str) -> Token {
+        let tokens = lexCode(code.to_string(), "test.bg").unwrap();
+        assert_eq!(tokens.len(), 1, "`{}` lexed as {:?}", code, tokens);
+        tokens[0].0.clone()
+    }
+
+    #[test]
+    fn digitsAreNumbers() {
+        assert_eq!(lexOne("1"), Token::Number);
+        assert_eq!(lexOne("0"), Token::Number);
+        assert_eq!(lexOne("10"), Token::Number);
+        assert_eq!(lexOne("1.5"), Token::Number);
+    }
+
+    #[test]
+    fn richNumbersAreOneToken() {
+        for number in ["0xFF", "0b1010", "0o17", "1_000_000", "1.5e-3", "2E10", "200u8", "3.0f32", "0xFFu16"] {
+            assert_eq!(lexOne(number), Token::Number, "`{}`", number);
+        }
+    }
+
+    #[test]
+    fn trueAndFalseAreBools() {
+        assert_eq!(lexOne("true"), Token::Bool);
+        assert_eq!(lexOne("FALSE"), Token::Bool);
+        assert_eq!(lexOne("trueish"), Token::Name);
+    }
+
+    #[test]
+    fn keywordsOnlyMatchWholeWords() {
+        assert_eq!(lexOne("if"), Token::ControlBlock);
+        assert_eq!(lexOne("iffy"), Token::Name);
+        assert_eq!(lexOne("print"), Token::BuiltIn);
+        assert_eq!(lexOne("printer"), Token::Name);
+        assert_eq!(lexOne("var"), Token::Var);
+        assert_eq!(lexOne("variable"), Token::Name);
+        assert_eq!(lexOne("AND"), Token::And);
+        assert_eq!(lexOne("and"), Token::Name);
+    }
+
+    #[test]
+    fn registeredFunctionsAreBuiltIns() {
+        let builtIns = ["clamp".to_string(), "if".to_string()];
+        let (tokens, _) = lexWithBuiltIns("clamp(if, clamped)".to_string(), "test.bg", &builtIns);
+        let kinds: Vec<Token> = tokens.into_iter().map(|(token, _, _)| token).collect();
+        assert_eq!(kinds[0], Token::BuiltIn);
+        assert_eq!(kinds[2], Token::ControlBlock);
+        assert_eq!(kinds[4], Token::Name);
+    }
+
+    #[test]
+    fn percentIsModulo() {
+        assert_eq!(lexOne("%"), Token::Modulo);
+    }
+
+    #[test]
+    fn dollarIsADataType() {
+        assert_eq!(lexOne("$"), Token::DataType);
+        assert_eq!(lexOne("@"), Token::DataType);
+    }
+
+    #[test]
+    fn blockCommentsNest() {
+        let tokens = lexCode(String::from("1 /* a /* b */ c */ * 2 / 3"), "test.bg").unwrap();
+        let kinds: Vec<Token> = tokens.iter().map(|(t, _, _)| t.clone()).collect();
+        assert_eq!(kinds, vec![
+            Token::Number, Token::BlockComment, Token::Multiplication, Token::Number, Token::Division, Token::Number,
+        ]);
+        assert_eq!(tokens[1].1, "/* a /* b */ c */");
     }
 
-    return tokens;
+    #[test]
+    fn unterminatedBlockCommentIsAnError() {
+        assert!(lexCode(String::from("1 /* a /* b */"), "test.bg").is_err());
+    }
+
+    #[test]
+    fn docCommentsAreKept() {
+        assert_eq!(lexOne("/// docs"), Token::DocComment);
+        assert_eq!(lexOne("// not docs"), Token::LineComment);
+    }
+
+    #[test]
+    fn everyReservedWordResolves() {
+        for (word, token) in RESERVED_WORDS {
+            assert_eq!(&lexOne(word), token, "`{}`", word);
+        }
+    }
+
+    #[test]
+    fn everyBadCharacterIsReported() {
+        let (tokens, errors) = lexRecovering("var a = 1 # 2;\n~a ->; `".to_string(), "test.bg");
+        assert_eq!(tokens.len(), 9);
+        let reported: Vec<(String, usize, usize, usize)> = errors
+            .iter()
+            .map(|error| {
+                let diagnostic = error.diagnostic();
+                let span = diagnostic.span.as_ref().unwrap();
+                (diagnostic.message.clone(), span.line, span.column, span.start)
+            })
+            .collect();
+        assert_eq!(reported, vec![
+            ("Unrecognized character `#`".to_string(), 1, 11, 10),
+            ("Unrecognized character `~`".to_string(), 2, 1, 15),
+            ("Unrecognized character ```".to_string(), 2, 8, 22),
+        ]);
+    }
 }
@@ -0,0 +1,24 @@
+#![allow(non_snake_case)]
+
+//! Jade as a library. `Interpreter` runs Jade code from Rust, the
+//! modules behind it are here too for tools that want one stage of the
+//! pipeline, like the `jade` CLI does for `tokens`, `ast` and `fmt`.
+
+pub mod lexer;
+pub mod parser;
+pub mod evaluator;
+pub mod runtime;
+pub mod jadeErrors;
+pub mod dump;
+pub mod formatter;
+pub mod checker;
+pub mod compiler;
+pub mod vm;
+pub mod resolver;
+pub mod optimizer;
+pub mod interpreter;
+pub mod convert;
+
+pub use interpreter::{Engine, EvalError, Interpreter};
+pub use jadeErrors::{Diagnostic, JadeError};
+pub use runtime::{Capture, Value};
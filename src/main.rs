@@ -1,27 +1,24 @@
 #![allow(non_snake_case)]
 
 
-// Import the packages
-mod lexer;
-mod parser;
-mod evaluator;
-mod runtime;
-mod jadeErrors;
-
+// Import the library, the CLI is a thin layer over it
+use jade_lang::{Engine, Interpreter, JadeError};
 
 // Import functions from lexer
-use lexer::openFile;
-use lexer::lexCode;
-use crate::lexer::Token;
+use jade_lang::lexer::openFile;
+use jade_lang::lexer::lexRecovering;
 
 // Import functions from parser
-use parser::chunkCode;
-use parser::parseCode;
-use parser::TokenBlock;
-use parser::ParsedBlock;
+use jade_lang::parser::parseRecovering;
+
+// Import the optimizer levels for --opt-level
+use jade_lang::optimizer::DEAD_BRANCHES;
 
-// Import functions from runtime
-use runtime::jruntime;
+// Import the dumps for `jade tokens` and `jade ast`
+use jade_lang::dump::{astJson, tokensJson};
+
+// Import the formatter for `jade fmt`
+use jade_lang::formatter::formatCode;
 
 /*
     AUTHOR:         Dyl C.
@@ -36,26 +33,134 @@ use runtime::jruntime;
                             
 */
 
+const USAGE: &str = "\
+Usage: jade [FILE.bg] [--vm]        run FILE.bg (firstTest.bg if not given)
+       jade run FILE [--vm]         run FILE, on the bytecode VM with --vm
+            [--opt-level=N]         0 runs the code as written, 1 folds
+                                    constants, 2 (the default) also drops
+                                    branches that can never run
+       jade tokens FILE [--json]    print the tokens FILE lexes into
+       jade ast FILE [--json]       print the statements FILE parses into
+       jade fmt FILE [--check]      format FILE in place, or with --check
+                                    only fail if it isn't formatted";
+
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let flag = |name: &str| args.iter().any(|arg| arg == name);
+    let (json, check) = (flag("--json"), flag("--check"));
+    let engine = if flag("--vm") { Engine::Vm } else { Engine::Tree };
+    let optLevel = match args.iter().find_map(|arg| arg.strip_prefix("--opt-level=")) {
+        None => DEAD_BRANCHES,
+        Some(level) => match level.parse::<u8>() {
+            Ok(level) if level <= DEAD_BRANCHES => level,
+            _ => {
+                eprintln!("Unknown opt level `{}`\n\n{}", level, USAGE);
+                std::process::exit(2);
+            }
+        },
+    };
+    let args: Vec<&str> = args.iter().map(String::as_str).filter(|arg| !arg.starts_with("--")).collect();
+
+    match args.as_slice() {
+        [] => runFile("firstTest.bg", engine, optLevel),
+        ["run", fileName] => runFile(fileName, engine, optLevel),
+        ["tokens", fileName] => dumpTokens(fileName, json),
+        ["ast", fileName] => dumpAst(fileName, json),
+        ["fmt", fileName] => formatFile(fileName, check),
+        [fileName] if fileName.ends_with(".bg") => runFile(fileName, engine, optLevel),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    }
+}
+
+// The Jade code in the file, or stop if it can't be read
+fn readSource(fileName: &str) -> String {
+    match openFile(fileName) {
+        Ok(rawCode) => rawCode,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    }
+}
 
-    // The Jade code extracted from the file. 
-    let rawCode: String = openFile("firstTest.bg");
-    
-    // The tokens extracted from the raw Code
-    let tokens: Vec<(Token, String)> = lexCode(rawCode);
+// Print every error pointing at its line, then stop
+fn reportErrors(errors: &[JadeError], rawCode: &str) -> ! {
+    for error in errors {
+        eprintln!("{}\n", error.render(rawCode));
+    }
+    if errors.len() > 1 {
+        eprintln!("Stopped after {} errors, nothing was run", errors.len());
+    }
+    std::process::exit(1);
+}
 
-    // Chunk the tokens into blocks that will be read by the parser
-    let tokenBlocks: Vec<TokenBlock> = chunkCode(tokens);
+fn runFile(fileName: &str, engine: Engine, optLevel: u8) {
+    // Run the code and stop cleanly if anything goes wrong,
+    // pointing at the line that caused it.
+    let mut interpreter = Interpreter::with_options(engine, optLevel);
+    if let Err(error) = interpreter.eval_file(fileName) {
+        reportErrors(&error.errors, &error.source);
+    }
+}
 
-    // Take the blocks and turn them into structs of data
-    let parsedCode: Vec<ParsedBlock> = parseCode(tokenBlocks);
-    
-    // At this point we'll enter runtime.
-    //jruntime(parsedCode);
-    
-    // At this point the code will run and do its thing.
+// `jade tokens`, the token stream lexCode produces
+fn dumpTokens(fileName: &str, json: bool) {
+    let rawCode: String = readSource(fileName);
+    let (tokens, errors) = lexRecovering(rawCode.clone(), fileName);
+    if !errors.is_empty() {
+        reportErrors(&errors, &rawCode);
+    }
 
-    // Last Step: Win. Hopefully.
+    if json {
+        println!("{}", tokensJson(&tokens).render());
+    } else {
+        for (token, text, span) in &tokens {
+            println!("{:>4}:{:<4} {:<16} {}", span.line, span.column, format!("{:?}", token), text);
+        }
+    }
+}
 
+// `jade ast`, the statements the parser produces
+fn dumpAst(fileName: &str, json: bool) {
+    let rawCode: String = readSource(fileName);
+    let (tokens, mut errors) = lexRecovering(rawCode.clone(), fileName);
+    let (parsedCode, parseErrors) = parseRecovering(tokens);
+    errors.extend(parseErrors);
+    if !errors.is_empty() {
+        reportErrors(&errors, &rawCode);
+    }
+
+    if json {
+        println!("{}", astJson(&parsedCode).render());
+    } else {
+        print!("{}", astJson(&parsedCode).tree());
+    }
 }
 
+// `jade fmt`, rewrite the file in the canonical style. Code that doesn't
+// lex or parse is left alone, its errors are reported instead.
+fn formatFile(fileName: &str, check: bool) {
+    let rawCode: String = readSource(fileName);
+    let (tokens, mut errors) = lexRecovering(rawCode.clone(), fileName);
+    let (_, parseErrors) = parseRecovering(tokens.clone());
+    errors.extend(parseErrors);
+    if !errors.is_empty() {
+        reportErrors(&errors, &rawCode);
+    }
+
+    let formatted = formatCode(&rawCode, &tokens);
+    if formatted == rawCode {
+        return;
+    }
+    if check {
+        eprintln!("{} is not formatted, run `jade fmt {}`", fileName, fileName);
+        std::process::exit(1);
+    }
+    if let Err(error) = std::fs::write(fileName, formatted) {
+        eprintln!("Could not write {}: {}", fileName, error);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,310 @@
+use crate::evaluator::evaluate_binary_op;
+use crate::parser::{BinaryOperator, Expression, ExpressionKind, Statement};
+use crate::runtime::Value;
+
+/// How much `optimize` does. Below `FOLD` the code is left as written,
+/// `DEAD_BRANCHES` also drops branches that can never run.
+pub const FOLD: u8 = 1;
+pub const DEAD_BRANCHES: u8 = 2;
+
+/// Rewrites the program so it does less work without changing what it
+/// does. Operations on literals are worked out ahead of time with the
+/// runtime's own `evaluate_binary_op`. Anything that would fail, like
+/// an overflow, is left alone so it still fails when (and only if) that
+/// code runs.
+pub fn optimize(code: Vec<Statement>, level: u8) -> Vec<Statement> {
+    if level < FOLD {
+        return code;
+    }
+    Optimizer { level }.block(code)
+}
+
+struct Optimizer {
+    level: u8,
+}
+
+impl Optimizer {
+    fn block(&self, code: Vec<Statement>) -> Vec<Statement> {
+        code.into_iter().flat_map(|statement| self.statement(statement)).collect()
+    }
+
+    // A statement can turn into none, or into the body of a branch
+    fn statement(&self, mut statement: Statement) -> Vec<Statement> {
+        self.fold(&mut statement);
+        if self.level >= DEAD_BRANCHES {
+            prune(statement)
+        } else {
+            vec![statement]
+        }
+    }
+
+    fn fold(&self, statement: &mut Statement) {
+        match statement {
+            Statement::VarDec { value, .. } |
+            Statement::VarSet { value, .. } |
+            Statement::FieldSet { value, .. } |
+            Statement::Expression { value, .. } => self.expression(value),
+
+            Statement::If { condition, body, otherwise, .. } => {
+                self.expression(condition);
+                *body = self.block(std::mem::take(body));
+                if let Some(otherwise) = otherwise {
+                    *otherwise = self.block(std::mem::take(otherwise));
+                }
+            }
+
+            Statement::Unless { condition, body, .. } |
+            Statement::While { condition, body, .. } |
+            Statement::Until { condition, body, .. } => {
+                self.expression(condition);
+                *body = self.block(std::mem::take(body));
+            }
+
+            Statement::For { init, condition, step, body, .. } => {
+                if let Some(init) = init {
+                    self.fold(init);
+                }
+                if let Some(condition) = condition {
+                    self.expression(condition);
+                }
+                if let Some(step) = step {
+                    self.fold(step);
+                }
+                *body = self.block(std::mem::take(body));
+            }
+
+            Statement::Print { values, target, .. } => {
+                for value in values.iter_mut().chain(target) {
+                    self.expression(value);
+                }
+            }
+
+            Statement::Append { value, target, .. } => {
+                self.expression(value);
+                self.expression(target);
+            }
+
+            Statement::Read { source, .. } => {
+                if let Some(source) = source {
+                    self.expression(source);
+                }
+            }
+
+            Statement::StructDec { .. } | Statement::EnumDec { .. } => {}
+        }
+    }
+
+    // Folds from the inside out. The span stays the same, so errors
+    // coercing the folded value still point at the whole expression.
+    fn expression(&self, expression: &mut Expression) {
+        let folded = match &mut expression.kind {
+            ExpressionKind::BinaryOp { op, left, right } => {
+                self.expression(left);
+                self.expression(right);
+                match (op, literal(left), literal(right)) {
+                    // `??` never looks at the right side unless the left is null
+                    (BinaryOperator::NullCoalesce, Some(Value::Null), _) => Some(right.kind.clone()),
+                    (BinaryOperator::NullCoalesce, Some(_), _) => Some(left.kind.clone()),
+                    (op, Some(l), Some(r)) => evaluate_binary_op(op, l, r).ok().and_then(literalKind),
+                    _ => None,
+                }
+            }
+
+            ExpressionKind::StructLiteral { fields, .. } => {
+                for (_, value) in fields {
+                    self.expression(value);
+                }
+                None
+            }
+
+            ExpressionKind::FieldAccess { object, .. } => {
+                self.expression(object);
+                None
+            }
+
+            ExpressionKind::EnumVariant { payload: values, .. } |
+            ExpressionKind::Call { args: values, .. } => {
+                for value in values {
+                    self.expression(value);
+                }
+                None
+            }
+
+            ExpressionKind::Match { subject, arms } => {
+                self.expression(subject);
+                for (_, arm) in arms {
+                    self.expression(arm);
+                }
+                None
+            }
+
+            ExpressionKind::Number(_) | ExpressionKind::String(_) | ExpressionKind::Boolean(_) |
+            ExpressionKind::Null | ExpressionKind::Variable { .. } => None,
+        };
+
+        if let Some(kind) = folded {
+            expression.kind = kind;
+        }
+    }
+}
+
+// Drops a branch whose condition is a constant. Variables declared in a
+// block live on after it, so a body that always runs can take the
+// branch's place.
+fn prune(statement: Statement) -> Vec<Statement> {
+    match statement {
+        Statement::If { condition: Expression { kind: ExpressionKind::Boolean(condition), .. }, body, otherwise, .. } => {
+            if condition { body } else { otherwise.unwrap_or_default() }
+        }
+        Statement::Unless { condition: Expression { kind: ExpressionKind::Boolean(condition), .. }, body, .. } => {
+            if condition { Vec::new() } else { body }
+        }
+        Statement::While { condition: Expression { kind: ExpressionKind::Boolean(false), .. }, .. } |
+        Statement::Until { condition: Expression { kind: ExpressionKind::Boolean(true), .. }, .. } => Vec::new(),
+        Statement::For { init, condition: Some(Expression { kind: ExpressionKind::Boolean(false), .. }), .. } => {
+            init.map(|init| vec![*init]).unwrap_or_default()
+        }
+        other => vec![other],
+    }
+}
+
+// The value of a literal, the same one the runtime would get
+fn literal(expression: &Expression) -> Option<Value> {
+    match &expression.kind {
+        ExpressionKind::Number(value) => Some(value.clone()),
+        ExpressionKind::String(text) => Some(Value::String(text.clone())),
+        ExpressionKind::Boolean(b) => Some(Value::Bool(*b)),
+        ExpressionKind::Null => Some(Value::Null),
+        _ => None,
+    }
+}
+
+// The literal that evaluates to `value`, if there is one
+fn literalKind(value: Value) -> Option<ExpressionKind> {
+    match value {
+        Value::String(text) => Some(ExpressionKind::String(text)),
+        Value::Bool(b) => Some(ExpressionKind::Boolean(b)),
+        Value::Null => Some(ExpressionKind::Null),
+        Value::Struct(..) | Value::Enum(..) => None,
+        number => Some(ExpressionKind::Number(number)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile;
+    use crate::jadeErrors::JadeError;
+    use crate::lexer::lexCode;
+    use crate::parser::parseCode;
+    use crate::resolver::resolve;
+    use crate::runtime::{runStatements, Capture, Runtime};
+    use crate::vm::Vm;
+
+    fn parsed(code: &str) -> Vec<Statement> {
+        parseCode(lexCode(code.to_string(), "test.bg").unwrap()).unwrap()
+    }
+
+    // How a run ended, what it printed and the value of each variable asked for
+    type Outcome = (Result<(), JadeError>, String, Vec<Option<Value>>);
+
+    // Runs the program at an opt level on both engines
+    fn run(code: &str, level: u8, names: &[&str]) -> Vec<Outcome> {
+        let mut program = optimize(parsed(code), level);
+
+        let treeOutput = Capture::new();
+        let mut runtime = Runtime::new();
+        runtime.set_output(treeOutput.clone());
+        resolve(&mut program, &mut runtime.names).unwrap();
+        let treeResult = runStatements(&program, &mut runtime);
+        let treeValues = names.iter().map(|name| runtime.get_variable(name).cloned()).collect();
+
+        let vmOutput = Capture::new();
+        let mut vmRuntime = Runtime::new();
+        vmRuntime.set_output(vmOutput.clone());
+        let chunk = compile(&program);
+        let mut vm = Vm::with_runtime(&chunk, vmRuntime);
+        let vmResult = vm.run();
+        let vmValues = names.iter().map(|name| vm.global(name).cloned()).collect();
+
+        vec![(treeResult, treeOutput.contents(), treeValues), (vmResult, vmOutput.contents(), vmValues)]
+    }
+
+    fn unchangedByOptimizing(code: &str, names: &[&str]) {
+        let expected = run(code, 0, names);
+        for level in [FOLD, DEAD_BRANCHES] {
+            assert_eq!(run(code, level, names), expected, "level {} changed `{}`", level, code);
+        }
+    }
+
+    // The value expression of the first statement
+    fn firstValue(code: &[Statement]) -> &ExpressionKind {
+        match &code[0] {
+            Statement::VarDec { value, .. } => &value.kind,
+            other => panic!("not a declaration: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn foldsConstantExpressions() {
+        let code = optimize(parsed("var day int32 = 60 * 60 * 24;"), FOLD);
+        assert_eq!(firstValue(&code), &ExpressionKind::Number(Value::Int64(86400)));
+
+        let code = optimize(parsed("var big = 2 > 1 == true;"), FOLD);
+        assert_eq!(firstValue(&code), &ExpressionKind::Boolean(true));
+
+        let code = optimize(parsed("var n int = null ?? 60 * 2;"), FOLD);
+        assert_eq!(firstValue(&code), &ExpressionKind::Number(Value::Int64(120)));
+    }
+
+    #[test]
+    fn leavesFailuresForTheRuntime() {
+        let code = optimize(parsed("var a = 9223372036854775807 + 1;"), FOLD);
+        assert!(matches!(firstValue(&code), ExpressionKind::BinaryOp { .. }));
+
+        let code = optimize(parsed("var a = 1 / 0;"), FOLD);
+        assert!(matches!(firstValue(&code), ExpressionKind::BinaryOp { .. }));
+    }
+
+    #[test]
+    fn dropsDeadBranches() {
+        let code = "var a = 1;
+            if (1 > 2) { a = 2; } elif (true) { a = 3; } else { a = 4; }
+            while (false) { a = 5; }
+            unless (true) { a = 6; }
+            for (var i = 0; false; i = i + 1) { a = 7; }";
+        let optimized = optimize(parsed(code), DEAD_BRANCHES);
+        assert_eq!(optimized.len(), 3);
+        assert!(matches!(&optimized[1], Statement::VarSet { name, .. } if name == "a"));
+        assert!(matches!(&optimized[2], Statement::VarDec { name, .. } if name == "i"));
+
+        // Folding alone keeps every branch
+        assert_eq!(optimize(parsed(code), FOLD).len(), 5);
+    }
+
+    #[test]
+    fn outputIsUnchanged() {
+        let code = "var day int32 = 60 * 60 * 24;
+            var total int64 = 0;
+            for (var i = 0; i < 10 + 10; i = i + 1) {
+                if (day > 1000 * 80) { total = total + 2 * 3; } else { total = total - 1; }
+                i, total ->;
+            }
+            if (false) { var never = 1; \"never\" ->; } elif (2 * 2 == 4) { var always = \"yes\"; always ->; }
+            until (true) { total = 0; \"until\" ->; }
+            var spare = null ?? 7 * 6;
+            spare, 60 * 2, day ->;";
+        unchangedByOptimizing(code, &["day", "total", "i", "never", "always", "spare"]);
+        let (_, printed, _) = &run(code, DEAD_BRANCHES, &[])[0];
+        assert!(printed.starts_with("0 6\n1 12\n"), "{}", printed);
+        assert!(printed.ends_with("19 120\nyes\n42 120 86400\n"), "{}", printed);
+    }
+
+    #[test]
+    fn failsTheSameWay() {
+        unchangedByOptimizing("var a int8 = 100 + 100;", &["a"]);
+        unchangedByOptimizing("var a = 1;\nif (false) { a = 1 / 0; }\na = 9223372036854775807 + 1;", &["a"]);
+        unchangedByOptimizing("var a int8 = 1;\nwhile (true) { a ->; a = a + 60 * 2; }", &["a"]);
+        unchangedByOptimizing("var a = 2;\nif (1 + 1) { a = 3; }", &["a"]);
+    }
+}
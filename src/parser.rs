@@ -1,244 +1,184 @@
-use crate::jadeErrors::parsingError;
-use crate::lexer::Token;
-
-/// A block of tokens. Will only contain the tokens pertaining to the
-/// block of block.
-#[derive(Debug)]
-pub struct TokenBlock {
-    pub tokens: Vec<(Token, String)>,
-}
+use crate::jadeErrors::{literalRangeError, nonExhaustiveMatchError, JadeError};
+use std::collections::HashMap;
+use crate::lexer::{Span, Token};
+use crate::runtime::Value;
 
-// Add some functionality to the TokenBlock Struct
-impl TokenBlock {
-    // Function that needs a mutable reference to self
-    fn addItem(&mut self, item: (Token, String)) {
-        self.tokens.push(item);
-    }
-}
-
-/// Chunks the code into pieces to be read by the parsCode fn.
-pub fn chunkCode (tokens: Vec<(Token,String)>) -> Vec<TokenBlock> {
-    // Setup variables we will use later for creating the parsed data.+
-    let mut tokenBlocks: Vec<TokenBlock> = Vec::new();
-    let mut currentBlock = TokenBlock { tokens: Vec::new() };
-    let mut braceDepth: u8 = 0;
-    // This shit is probably confusing so lemme break it down
-    // We loop through token:value pairs and do a case statement
-    // We increment or decrement braceDepth based on the token type
-    // We also check for semicolons to determine if the line should
-    // end the block.
-    for t in tokens {
-        match t.1.as_str() {
-            
-            // increment braceDepth to enter a block of code
-            "{" => {
-                if braceDepth == 0 {
-                    currentBlock.addItem(t);
-                }
-                braceDepth += 1;},
-            
-            // decrement braceDepth to exit the Layer of code0
-            "}" => {
-                braceDepth -= 1;
-                if braceDepth == 0 {
-                    currentBlock.addItem(t);
-                    tokenBlocks.push(currentBlock);
-                    currentBlock = TokenBlock { tokens: Vec::new() }; // fresh block
-                }},
-            ";" => {if braceDepth == 0 {
-                currentBlock.addItem(t);
-                tokenBlocks.push(currentBlock);
-                currentBlock = TokenBlock { tokens: Vec::new() }; // fresh block
-            }},
-            _   => currentBlock.addItem(t)
-        }
-    }
-    return tokenBlocks;
+/// Which keyword a declaration used.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeclKind {
+    Var,
+    Const,
+    Sink,
 }
 
-/// A Struct for each different possible property of a parsed block.
-#[derive(Debug, Default)]
-#[allow(dead_code)]
-pub struct ParsedBlock {
-    pub blockType: String,
-    pub identifier: String,
-    pub name: Option<String>,
-    pub datatype: Option<String>,
-    pub value: Option<Expression>,
-    pub condition: Option<Expression>,
-    pub body: Option<Vec<ParsedBlock>>, // Nested blocks after parsing
-    pub parameters: Option<Vec<String>>,
-    pub returnType: Option<String>,
+/// A statement, one per construct in the language. Each variant holds
+/// only what that construct needs plus the span it covers, so tools can
+/// match on them without guessing which fields are filled in.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    /// `var name type = value;`, also `const` and `sink`. The type can
+    /// be left out, then it comes from the value. A sink never has one,
+    /// it holds the name of the file `>>` appends to.
+    VarDec {
+        kind: DeclKind,
+        name: String,
+        datatype: Option<String>,
+        value: Expression,
+        doc: Option<String>,
+        slot: Option<usize>, // The global it's stored in, from the resolver
+        span: Span,
+    },
+    /// `name = value;`
+    VarSet {
+        name: String,
+        value: Expression,
+        slot: Option<usize>,
+        span: Span,
+    },
+    /// `name.field.field = value;`
+    FieldSet {
+        name: String,
+        path: Vec<String>,
+        value: Expression,
+        slot: Option<usize>,
+        span: Span,
+    },
+    /// `value, ... ->;` prints the values on one line, `value -> file;`
+    /// writes them to the file instead
+    Print {
+        values: Vec<Expression>,
+        target: Option<Expression>,
+        span: Span,
+    },
+    /// `value >> target;` appends to the array, string or sink variable
+    /// `target`, or to the end of the file it names
+    Append {
+        value: Expression,
+        target: Expression,
+        span: Span,
+    },
+    /// `name << file;` reads the file into the variable, `name <<;` reads
+    /// a line of input
+    Read {
+        name: String,
+        source: Option<Expression>,
+        slot: Option<usize>,
+        span: Span,
+    },
+    /// `function(args);`, a call run for what it does
+    Expression {
+        value: Expression,
+        span: Span,
+    },
+    /// `struct Name { field type; ... }`
+    StructDec {
+        name: String,
+        fields: Vec<(String, String)>, // Field name and type
+        doc: Option<String>,
+        span: Span,
+    },
+    /// `enum Name { Variant(type, ...), ... }`
+    EnumDec {
+        name: String,
+        variants: Vec<(String, Vec<String>)>, // Variant name and payload types
+        doc: Option<String>,
+        span: Span,
+    },
+    /// `if (condition) { ... }`, an `elif` is another `If` as the only
+    /// statement in `otherwise`
+    If {
+        condition: Expression,
+        body: Vec<Statement>,
+        otherwise: Option<Vec<Statement>>,
+        span: Span,
+    },
+    /// `unless (condition) { ... }`, runs when the condition is false
+    Unless {
+        condition: Expression,
+        body: Vec<Statement>,
+        span: Span,
+    },
+    /// `while (condition) { ... }`
+    While {
+        condition: Expression,
+        body: Vec<Statement>,
+        span: Span,
+    },
+    /// `until (condition) { ... }`, loops while the condition is false
+    Until {
+        condition: Expression,
+        body: Vec<Statement>,
+        span: Span,
+    },
+    /// `for (init; condition; step) { ... }`, every part is optional
+    For {
+        init: Option<Box<Statement>>,
+        condition: Option<Expression>,
+        step: Option<Box<Statement>>,
+        body: Vec<Statement>,
+        span: Span,
+    },
 }
 
-/// Takes the chunked code and breaks it down even further.
-/// It only takes what is nessesary and returns a Vec of structs. 
-pub fn parseCode(tokenBlocks: Vec<TokenBlock>) -> Vec<ParsedBlock> {
-    let mut parsedCode: Vec<ParsedBlock> = Vec::new();
-    //println!("{:#?}", tokenBlocks);
-    for block in tokenBlocks {
-        
-        let tokens: &Vec<(Token, String)> = &block.tokens;
-
-        if tokens.is_empty() {
-            
-            continue;
-        }
-
-        match tokens[0].0 {
-            Token::Var |
-            Token::Const |
-            Token::Sink         => {
-                
-                // Handle variable declaration as before
-                let identifier: String = tokens.get(0).map(|(_, v)| v.clone()).unwrap_or_default();
-                let name: Option<String> = tokens.get(1).map(|(_, v)| v.clone());
-                let datatype: Option<String> = Some(tokens.get(2).map(|(_, v)| v.clone()).unwrap());
-
-                // Length of the expression/value we are setting the
-                // variable to
-                let length: usize = tokens.len();
-                let eqSign = tokens.iter().position(|(v, _)| v == &Token::Equals).unwrap_or(length);
-                
-                // Vec containing the expression
-                let newExpression = tokens[eqSign + 1..length].to_vec();
-
-                parsedCode.push(ParsedBlock {
-                    blockType: String::from("VarDec"),
-                    identifier,
-                    name,
-                    datatype,
-                    value: Some(evaluateExpression(newExpression)),
-                    ..Default::default()
-                });
-            }
-            
-            Token::Name         => {
-                // Variable assignment
-                if tokens.get(1).map(|(v, _)| v).unwrap() == &(Token::Equals) {
-                    
-                    // Handle variable assignment (setting new value)
-                    // Get the variable name
-                    let varName = tokens.get(0).map(|(_, v)| v.clone()).unwrap_or_default();
-
-                    // Length of the expression/value we are setting the
-                    // variable to
-                    let length: usize = tokens.len();
-
-                    // Vec containing the expression
-                    let newExpression = tokens[2..length].to_vec();
-        
-                        parsedCode.push(ParsedBlock {
-                        blockType: String::from("VarSet"),
-                        name: Some(varName),
-                        value: Some(evaluateExpression(newExpression)),
-                        ..Default::default()
-                    });
-                    
-                // Print using "->" operator
-                } else if tokens.get(1).map(|(v, _)| v).unwrap() == &(Token::Print) {
-                    let varName= tokens.get(0).map(|(_, v)| v.clone()).unwrap_or_default();
-                    
-                    parsedCode.push(ParsedBlock {
-                        blockType: String::from("PrintVar"),
-                        name: Some(varName),
-                        ..Default::default()
-                    })
-                }
-            }
-            
-            Token::ControlBlock => {
-                // Use this to visualize control blocks.
-                //print!("\n\n{:?}\n\n", block)
-
-                // Define Variables for Control Blocks
-                // We need to separate the identifier, condition, and the 
-                // the body.
-                    
-                let identifier = tokens.get(0).map
-                (|(_, v)| v.clone())
-                .unwrap_or_default(); // What type of Control Block we are dealing with.
-                let mut currentDepth: i8 = 0; // The depth. Can be used for either Condition or body.
-                let mut condition: Vec<(Token, String)> = Vec::new(); // The condition we will be checking.
-                let mut bodyTokens: Vec<(Token, String)> = Vec::new();
-                let blockBody: Vec<TokenBlock>; // The code that will run based on the conditions.
-                let mut conditionDone: bool = false; // Are we done finding the condition?
-
-                // Loop and determine the each part of the block.
-                for (idx, tkn) in tokens.into_iter().enumerate() {
-                    match tkn.0 {
-                        
-                        Token::OpenParen | Token::OpenBrace
-                            => {
-                                if currentDepth == 0 && identifier == "else" {
-                                    conditionDone = true;
-                                }
-                                currentDepth += 1
-                            }
-
-                        Token::CloseParen| Token::CloseBrace
-                            => {
-                                currentDepth -= 1;
-
-                                // Check to see if we are ending the condition.
-                                if tkn.0.clone() == Token::CloseParen &&
-                                tokens.get(idx + 1).unwrap().0 == Token::OpenBrace &&
-                                conditionDone == false {
-                                    
-                                    conditionDone = true
-                                }
-                            }
-
-                        _   => {
-                                
-                                if currentDepth > 0 && conditionDone == false {
-                                    condition.push(tkn.clone());
-                                } else if currentDepth > 0 && conditionDone {
-                                    bodyTokens.push(tkn.clone());
-                                }
-                            }
-                    }
-                }
-
-                // Set body to the correct tokens and correct format
-                blockBody = chunkCode(bodyTokens);
-                
-                // We check to see what flavor of control block it is
-                // then we return based on what we find.
-                if identifier == "if"  || identifier == "elif" ||
-                identifier == "unless" || identifier == "for"  ||
-                identifier == "while"  || identifier == "until"  {
-
-                    parsedCode.push(ParsedBlock {
-                    blockType: String::from("ConBlock"),
-                    identifier,
-                    condition: Some(evaluateExpression(condition)),
-                    body: Some(parseCode(blockBody)),
-                    ..Default::default()
-                    });
-                } else {
-                    
-                    parsedCode.push(ParsedBlock {
-                    blockType: String::from("ConBlock"),
-                    identifier,
-                    ..Default::default()
-                    });
-                }
-            }
-
-            Token::Comment     => {
-                if tokens.get(1).map(|(v, _)| v.clone()) == Some(Token::Division) {
+impl Statement {
+    /// Where the whole statement is in the source.
+    pub fn span(&self) -> &Span {
+        match self {
+            Statement::VarDec { span, .. } |
+            Statement::VarSet { span, .. } |
+            Statement::FieldSet { span, .. } |
+            Statement::Print { span, .. } |
+            Statement::Append { span, .. } |
+            Statement::Read { span, .. } |
+            Statement::Expression { span, .. } |
+            Statement::StructDec { span, .. } |
+            Statement::EnumDec { span, .. } |
+            Statement::If { span, .. } |
+            Statement::Unless { span, .. } |
+            Statement::While { span, .. } |
+            Statement::Until { span, .. } |
+            Statement::For { span, .. } => span,
+        }
+    }
 
-                }
-            }
+    /// The expressions directly in this statement, not in its body.
+    pub fn expressions(&self) -> Vec<&Expression> {
+        match self {
+            Statement::VarDec { value, .. } |
+            Statement::VarSet { value, .. } |
+            Statement::FieldSet { value, .. } |
+            Statement::Expression { value, .. } => vec![value],
+            Statement::If { condition, .. } |
+            Statement::Unless { condition, .. } |
+            Statement::While { condition, .. } |
+            Statement::Until { condition, .. } => vec![condition],
+            Statement::For { condition, .. } => condition.iter().collect(),
+            Statement::Print { values, target, .. } => values.iter().chain(target).collect(),
+            Statement::Append { value, target, .. } => vec![value, target],
+            Statement::Read { source, .. } => source.iter().collect(),
+            Statement::StructDec { .. } | Statement::EnumDec { .. } => Vec::new(),
+        }
+    }
 
-            _ => {
-                println!("Unrecognized block: {:?}", tokens);
+    /// The statements nested inside this one.
+    pub fn children(&self) -> Vec<&Statement> {
+        match self {
+            Statement::If { body, otherwise, .. } => body.iter().chain(otherwise.iter().flatten()).collect(),
+            Statement::Unless { body, .. } |
+            Statement::While { body, .. } |
+            Statement::Until { body, .. } => body.iter().collect(),
+            Statement::For { init, step, body, .. } => {
+                init.iter().map(|s| &**s).chain(body.iter()).chain(step.iter().map(|s| &**s)).collect()
             }
+            _ => Vec::new(),
         }
     }
+}
 
-    parsedCode
+// The text of a `///` line without the slashes
+fn docText(comment: &str) -> String {
+    let text = comment.trim_start_matches('/');
+    text.strip_prefix(' ').unwrap_or(text).trim_end().to_string()
 }
 
 /// A struct for the binary operators. This will be used to parse the
@@ -268,139 +208,1187 @@ pub enum BinaryOperator {
     Xnor,
     And,
     Or,
+    NullCoalesce,
 }
 
-/// A struct for the binary operators. This will be used to parse the
-/// binary operators in the code.
+/// Where a variable lives, worked out by the resolver. `depth` 0 is
+/// the globals, each `match` arm around a use adds one for the names it
+/// binds, and `index` is the place in that scope.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Slot {
+    pub depth: usize,
+    pub index: usize,
+}
+
+/// An expression and where it is in the source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    pub kind: ExpressionKind,
+    pub span: Span,
+}
+
+/// The different kinds of expression.
 #[allow(dead_code)]
 #[derive(Debug, Clone, PartialEq)]
-pub enum Expression {
-    Float(f64),
-    Integer(i64),
+pub enum ExpressionKind {
+    /// A number literal, already the width its suffix asked for
+    Number(Value),
     String(String),
     Boolean(bool),
-    Variable(String),
+    Null,
+    /// A variable, `slot` is `None` until the resolver has run
+    Variable {
+        name: String,
+        slot: Option<Slot>,
+    },
     BinaryOp {
         op: BinaryOperator,
         left: Box<Expression>,
         right: Box<Expression>,
     },
-    // Add more as needed: UnaryOp, Call, Index, etc.
+    /// `Name { field = value, ... }`
+    StructLiteral {
+        name: String,
+        fields: Vec<(String, Expression)>,
+    },
+    /// `object.field`, or `object?.field` when `optional`
+    FieldAccess {
+        object: Box<Expression>,
+        field: String,
+        optional: bool,
+    },
+    /// `Enum::Variant` or `Enum::Variant(payload, ...)`
+    EnumVariant {
+        enumName: String,
+        variant: String,
+        payload: Vec<Expression>,
+    },
+    /// `match (subject) { pattern => expression, ... }`
+    Match {
+        subject: Box<Expression>,
+        arms: Vec<(Pattern, Expression)>,
+    },
+    /// `function(arg, ...)`, a built-in or a function registered from Rust
+    Call {
+        name: String,
+        args: Vec<Expression>,
+    },
+    // Add more as needed: UnaryOp, Index, etc.
+}
+
+/// The patterns a `match` arm can test against.
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    /// `_`, matches anything
+    Wildcard,
+    /// A bare name, matches anything and binds it
+    Binding(String),
+    /// A number, string or bool literal
+    Literal(Expression),
+    /// `start..end` or `start..=end`
+    Range {
+        start: Expression,
+        end: Expression,
+        inclusive: bool,
+    },
+    /// `Enum::Variant(pattern, ...)`
+    Variant {
+        enumName: String,
+        variant: String,
+        payload: Vec<Pattern>,
+    },
 }
 
-fn evaluateExpression (expressionTokens: Vec<(Token, String)>) -> Expression {
-    let expression: Expression;
-    let mut tokens = expressionTokens.clone();
-    
-    // Set the length of the expression
-    let expLen: usize;
-    if tokens[tokens.len() - 1].0 == Token::Semicolon {
-        tokens.pop();
+impl Pattern {
+    // Patterns that match every value
+    fn isIrrefutable(&self) -> bool {
+        matches!(self, Pattern::Wildcard | Pattern::Binding(_))
     }
-    expLen = tokens.len();
 
-    //println!("DEBUG: ExpressioN: \n{:?}", expressionTokens);
+    /// The names the pattern binds, in the order they're bound.
+    pub fn bindings(&self) -> Vec<&String> {
+        match self {
+            Pattern::Binding(name) => vec![name],
+            Pattern::Variant { payload, .. } => payload.iter().flat_map(Pattern::bindings).collect(),
+            Pattern::Wildcard | Pattern::Literal(_) | Pattern::Range { .. } => Vec::new(),
+        }
+    }
+}
 
-    // We either use the value given (if it's a single token) or
-    // we create a binary-op
-    if expLen == 1 {
-        expression = evalToken(expressionTokens.get(0).unwrap().clone());
+/// The grammar the parser follows, one function per rule:
+///
+/// ```text
+/// program     = statement* EOF
+/// statement   = varDec | structDec | enumDec | if | loop | for | simple ";"
+/// varDec      = ("var" | "const" | "sink") NAME type? "=" expression
+/// structDec   = "struct" NAME "{" (NAME type (";" | ",")?)* "}"
+/// enumDec     = "enum" NAME "{" (NAME ("(" type ("," type)* ")")? (";" | ",")?)* "}"
+/// if          = "if" "(" expression ")" block
+///               ("elif" "(" expression ")" block)* ("else" (if | block))?
+/// loop        = ("unless" | "while" | "until") "(" expression ")" block
+/// for         = "for" "(" (varDec | simple)? ";" expression? ";" simple? ")" block
+///             | "for" "(" expression ")" block
+/// simple      = NAME "=" expression | NAME ("." NAME)+ "=" expression | NAME "<<" expression?
+///             | output | expression ">>" expression | call
+/// output      = expression ("," expression)* "->" expression?
+/// block       = "{" statement* "}"
+/// type        = (NAME | "$" | "@") "?"?
+///
+/// expression  = coalesce
+/// coalesce    = or ("??" or)*
+/// or          = and (("||" | "OR" | "XOR" | "NOR" | "XNOR") and)*
+/// and         = equality (("&&" | "AND" | "NAND") equality)*
+/// equality    = comparison (("==" | "!=" | "===") comparison)*
+/// comparison  = term (("<" | ">" | "<=" | ">=") term)*
+/// term        = factor (("+" | "-") factor)*
+/// factor      = power (("*" | "/" | "%") power)*
+/// power       = postfix (("**" | "^/") power)?
+/// postfix     = primary (("." | "?.") NAME)*
+/// primary     = literal | NAME | NAME "{" (NAME "=" expression ","?)* "}"
+///             | NAME "::" NAME ("(" expression ("," expression)* ")")?
+///             | "match" "(" expression ")" "{" (pattern "=>" expression ","?)* "}"
+///             | "(" expression ")" | call
+/// call        = (BUILTIN | NAME) "(" (expression ("," expression)*)? ")"
+/// pattern     = "_" | NAME | NAME "::" NAME ("(" pattern ("," pattern)* ")")?
+///             | literal ((".." | "..=") literal)?
+/// ```
+struct Parser {
+    tokens: Vec<(Token, String, Span)>,
+    pos: usize,
+    docs: HashMap<usize, Vec<String>>, // `///` lines before the token at each index
+    errors: Vec<JadeError>,
+}
+
+type Parsed<T> = Result<T, JadeError>;
+
+/// Parses the tokens into statements. A statement that doesn't parse is
+/// reported and skipped so the rest of the file still gets checked.
+pub fn parseCode(tokens: Vec<(Token, String, Span)>) -> Result<Vec<Statement>, Vec<JadeError>> {
+    let (program, errors) = parseRecovering(tokens);
+    if errors.is_empty() {
+        Ok(program)
     } else {
-        let mut op: BinaryOperator = BinaryOperator::Not;
-        let mut opIdx: usize = 0;
-        let mut parenDepth: u8 = 0;
-        for (idx, token) in expressionTokens.iter().enumerate() {
-            //println!("Token: {:?}\tValue: {:?}\nIndex: {:?}\tParenDepth: {:?}\n", token.0, token.1, idx, parenDepth);
-           
+        Err(errors)
+    }
+}
+
+/// Like parseCode, but also hands back the statements that did parse so
+/// later checks can look for more mistakes in them.
+pub fn parseRecovering(tokens: Vec<(Token, String, Span)>) -> (Vec<Statement>, Vec<JadeError>) {
+    let mut parser = Parser::new(tokens);
+    let mut program: Vec<Statement> = Vec::new();
+    while !parser.atEnd() {
+        if let Some(statement) = parser.statementOrRecover() {
+            program.push(statement);
+        }
+    }
+    (program, parser.errors)
+}
+
+impl Parser {
+    // Doc comments are pulled out of the stream and remembered by the
+    // token they come before, everywhere else they'd be in the way.
+    // Other comments are dropped.
+    fn new(allTokens: Vec<(Token, String, Span)>) -> Self {
+        let mut tokens: Vec<(Token, String, Span)> = Vec::new();
+        let mut docs: HashMap<usize, Vec<String>> = HashMap::new();
+        for token in allTokens {
             match token.0 {
-                Token::OpenParen  => parenDepth += 1,
-                Token::CloseParen => parenDepth -= 1,
-                Token::Addition|Token::Subtraction|Token::Division|Token::Multiplication|
-                Token::Greater|Token::GreaterOEqual|Token::Less|Token::LessOEqual|Token::Equality|
-                Token::Exponent|Token::NotEqual|Token::TrueEquality|Token::Root
-                => {
-                    if parenDepth == 0 {
-                        op = match token.0 {
-                            Token::Addition => BinaryOperator::Add,
-                            Token::Subtraction => BinaryOperator::Subtract,
-                            Token::Division => BinaryOperator::Divide,
-                            Token::Multiplication => BinaryOperator::Multiply,
-                            Token::Greater => BinaryOperator::GreaterThan,
-                            Token::GreaterOEqual => BinaryOperator::GreaterOrEqual,
-                            Token::Less => BinaryOperator::LessThan,
-                            Token::LessOEqual => BinaryOperator::LessOrEqual,
-                            Token::Equality => BinaryOperator::Equal,
-                            Token::Exponent => BinaryOperator::Exponent,
-                            Token::Modulo => BinaryOperator::Modulo,
-                            Token::And => BinaryOperator::And,
-                            Token::Or => BinaryOperator::Or,
-                            Token::Not => BinaryOperator::Not,
-                            Token::NotEqual => BinaryOperator::NotEqual,
-                            Token::TrueEquality => BinaryOperator::TrueEquality,
-                            Token::Root => BinaryOperator::Root,
-                            Token::Xor => BinaryOperator::Xor,
-                            Token::Nor => BinaryOperator::Nor,
-                            Token::Nand => BinaryOperator::Nand,
-                            Token::Xnor => BinaryOperator::Xnor,
-                            _ => {
-                                parsingError();
-                                BinaryOperator::Or}
-                        };
-                        opIdx = idx;
+                Token::DocComment => docs.entry(tokens.len()).or_default().push(docText(&token.1)),
+                Token::LineComment | Token::BlockComment => {}
+                _ => tokens.push(token),
+            }
+        }
+        Parser { tokens, pos: 0, docs, errors: Vec::new() }
+    }
+
+    // TOKEN HELPERS
+
+    fn atEnd(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _, _)| t)
+    }
+
+    fn peekAt(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + offset).map(|(t, _, _)| t)
+    }
+
+    fn check(&self, token: &Token) -> bool {
+        self.peek() == Some(token)
+    }
+
+    fn advance(&mut self) -> (Token, String, Span) {
+        let token = self.tokens[self.pos].clone();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.check(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    // The span of the current token, or of the end of the file
+    fn here(&self) -> Span {
+        match self.tokens.get(self.pos).or(self.tokens.last()) {
+            Some((_, _, span)) => span.clone(),
+            None => Span::default(),
+        }
+    }
+
+    // The span from the token at `start` to the last one consumed
+    fn spanFrom(&self, start: usize) -> Span {
+        let last = self.pos.saturating_sub(1).max(start);
+        match (self.tokens.get(start), self.tokens.get(last)) {
+            (Some(first), Some(last)) => first.2.to(&last.2),
+            _ => self.here(),
+        }
+    }
+
+    // An error pointing at the current token
+    fn unexpected(&self, expected: &str) -> JadeError {
+        match self.tokens.get(self.pos) {
+            Some(found) => JadeError::Parse(format!("Expected {}, found `{}`", expected, found.1).into())
+                .at(found.2.clone()),
+            None => JadeError::Parse(format!("Expected {}, found the end of the file", expected).into())
+                .at(self.here()),
+        }
+    }
+
+    fn expect(&mut self, token: Token, expected: &str) -> Parsed<(Token, String, Span)> {
+        if self.check(&token) {
+            Ok(self.advance())
+        } else {
+            Err(self.unexpected(expected))
+        }
+    }
+
+    fn expectName(&mut self, expected: &str) -> Parsed<String> {
+        Ok(self.expect(Token::Name, expected)?.1)
+    }
+
+    // ERROR RECOVERY
+
+    // Parses a statement, or records the error and skips to where the
+    // next statement should start.
+    fn statementOrRecover(&mut self) -> Option<Statement> {
+        let start = self.pos;
+        match self.statement() {
+            Ok(statement) => Some(statement),
+            Err(error) => {
+                self.errors.push(error);
+                self.synchronize(start);
+                None
+            }
+        }
+    }
+
+    // Skips the statement starting at `start`: up to and including a `;`
+    // outside any brackets, or the `}` closing a block statement (and
+    // any `elif`/`else` after it). Stops before a `}` that closes the
+    // enclosing block. Always moves forward at least one token.
+    fn synchronize(&mut self, start: usize) {
+        let endsOnBrace = matches!(
+            self.tokens.get(start).map(|(t, _, _)| t),
+            Some(Token::ControlBlock | Token::Struct | Token::Enum)
+        );
+        self.pos = start;
+        let mut depth: usize = 0;
+        while let Some(token) = self.peek().cloned() {
+            match token {
+                Token::OpenParen | Token::OpenBrace => depth += 1,
+                Token::CloseParen => depth = depth.saturating_sub(1),
+                Token::CloseBrace if depth == 0 => {
+                    if self.pos == start {
+                        self.pos += 1;
+                    }
+                    return;
+                }
+                Token::CloseBrace => {
+                    depth -= 1;
+                    let chained = matches!(self.tokens.get(self.pos + 1), Some((Token::ControlBlock, k, _)) if k == "elif" || k == "else");
+                    if depth == 0 && endsOnBrace && !chained {
+                        self.pos += 1;
+                        return;
+                    }
+                }
+                Token::Semicolon if depth == 0 => {
+                    self.pos += 1;
+                    return;
+                }
+                _ => {}
+            }
+            self.pos += 1;
+        }
+    }
+
+    // STATEMENTS
+
+    fn statement(&mut self) -> Parsed<Statement> {
+        let doc = self.docs.get(&self.pos).map(|lines| lines.join("\n"));
+        let start = self.pos;
+        match self.peek() {
+            Some(Token::Var | Token::Const | Token::Sink) => {
+                let statement = self.varDec(doc)?;
+                self.expect(Token::Semicolon, "`;`")?;
+                Ok(statement)
+            }
+            Some(Token::Struct) => self.structDec(doc),
+            Some(Token::Enum) => self.enumDec(doc),
+            Some(Token::ControlBlock) => {
+                let keyword = self.tokens[self.pos].1.clone();
+                match keyword.as_str() {
+                    "if" => self.ifStatement(),
+                    "unless" | "while" | "until" => self.loopStatement(),
+                    "for" => self.forStatement(),
+                    "match" => {
+                        let statement = self.simple()?;
+                        self.expect(Token::Semicolon, "`;`")?;
+                        Ok(statement)
                     }
+                    "elif" | "else" => Err(JadeError::Parse(format!("`{}` needs an `if` right before it", keyword).into())
+                        .at(self.here())),
+                    _ => Err(JadeError::Parse(format!("`{}` can't start a statement", keyword).into()).at(self.here())),
                 }
+            }
+            Some(Token::Name | Token::BuiltIn | Token::OpenParen | Token::String | Token::Number | Token::Bool | Token::Null) => {
+                let statement = self.simple()?;
+                self.expect(Token::Semicolon, "`;`")?;
+                Ok(statement)
+            }
+            _ => {
+                let error = self.unexpected("a statement");
+                self.pos = start;
+                Err(error)
+            }
+        }
+    }
+
+    fn varDec(&mut self, doc: Option<String>) -> Parsed<Statement> {
+        let start = self.pos;
+        let kind = match self.advance().0 {
+            Token::Const => DeclKind::Const,
+            Token::Sink => DeclKind::Sink,
+            _ => DeclKind::Var,
+        };
+        let name = self.expectName("a variable name")?;
+        let datatype = match kind {
+            DeclKind::Sink => Some(String::from("sink")),
+            _ if self.check(&Token::Equals) => None,
+            _ => Some(self.typeName()?),
+        };
+        self.expect(Token::Equals, "`=`")?;
+        let value = self.expression()?;
+        Ok(Statement::VarDec { kind, name, datatype, value, doc, slot: None, span: self.spanFrom(start) })
+    }
+
+    // `int`, `Point`, `$`, and any of them with a `?` for nullable
+    fn typeName(&mut self) -> Parsed<String> {
+        let mut name = match self.peek() {
+            Some(Token::Name | Token::DataType) => self.advance().1,
+            _ => return Err(self.unexpected("a type")),
+        };
+        if self.eat(&Token::Question) {
+            name.push('?');
+        }
+        Ok(name)
+    }
+
+    fn structDec(&mut self, doc: Option<String>) -> Parsed<Statement> {
+        let start = self.pos;
+        self.advance();
+        let name = self.expectName("a struct name")?;
+        self.expect(Token::OpenBrace, "`{`")?;
+
+        let mut fields: Vec<(String, String)> = Vec::new();
+        while !self.check(&Token::CloseBrace) {
+            let field = self.expectName("a field name or `}`")?;
+            fields.push((field, self.typeName()?));
+            if !self.eat(&Token::Semicolon) && !self.eat(&Token::Comma) {
+                break;
+            }
+        }
+        self.expect(Token::CloseBrace, "`;` or `}`")?;
+
+        Ok(Statement::StructDec { name, fields, doc, span: self.spanFrom(start) })
+    }
+
+    fn enumDec(&mut self, doc: Option<String>) -> Parsed<Statement> {
+        let start = self.pos;
+        self.advance();
+        let name = self.expectName("an enum name")?;
+        self.expect(Token::OpenBrace, "`{`")?;
+
+        // Each variant is a name and maybe a list of payload types
+        let mut variants: Vec<(String, Vec<String>)> = Vec::new();
+        while !self.check(&Token::CloseBrace) {
+            let variant = self.expectName("a variant name or `}`")?;
+            let mut payload: Vec<String> = Vec::new();
+            if self.eat(&Token::OpenParen) {
+                loop {
+                    payload.push(self.typeName()?);
+                    if !self.eat(&Token::Comma) {
+                        break;
+                    }
+                }
+                self.expect(Token::CloseParen, "`,` or `)`")?;
+            }
+            variants.push((variant, payload));
+            if !self.eat(&Token::Comma) && !self.eat(&Token::Semicolon) {
+                break;
+            }
+        }
+        self.expect(Token::CloseBrace, "`,` or `}`")?;
+
+        Ok(Statement::EnumDec { name, variants, doc, span: self.spanFrom(start) })
+    }
+
+    // `( expression )` after a control keyword
+    fn condition(&mut self) -> Parsed<Expression> {
+        self.expect(Token::OpenParen, "`(`")?;
+        let condition = self.expression()?;
+        self.expect(Token::CloseParen, "`)`")?;
+        Ok(condition)
+    }
+
+    fn block(&mut self) -> Parsed<Vec<Statement>> {
+        let open = self.expect(Token::OpenBrace, "`{`")?;
+        let mut body: Vec<Statement> = Vec::new();
+        while !self.check(&Token::CloseBrace) {
+            if self.atEnd() {
+                return Err(JadeError::Parse(String::from("This `{` is never closed").into()).at(open.2));
+            }
+            if let Some(statement) = self.statementOrRecover() {
+                body.push(statement);
+            }
+        }
+        self.advance();
+        Ok(body)
+    }
+
+    // Is the current token the control keyword `keyword`?
+    fn checkKeyword(&self, keyword: &str) -> bool {
+        matches!(self.tokens.get(self.pos), Some((Token::ControlBlock, k, _)) if k == keyword)
+    }
+
+    fn ifStatement(&mut self) -> Parsed<Statement> {
+        let start = self.pos;
+        self.advance();
+        let condition = self.condition()?;
+        let body = self.block()?;
 
-                _=> {}
+        // `elif` and `else if` are another `if` in the else branch
+        let otherwise = if self.checkKeyword("elif") {
+            Some(vec![self.ifStatement()?])
+        } else if self.checkKeyword("else") {
+            self.advance();
+            if self.checkKeyword("if") {
+                Some(vec![self.ifStatement()?])
+            } else {
+                Some(self.block()?)
+            }
+        } else {
+            None
+        };
+
+        Ok(Statement::If { condition, body, otherwise, span: self.spanFrom(start) })
+    }
+
+    fn loopStatement(&mut self) -> Parsed<Statement> {
+        let start = self.pos;
+        let keyword = self.advance().1;
+        let condition = self.condition()?;
+        let body = self.block()?;
+        let span = self.spanFrom(start);
+        Ok(match keyword.as_str() {
+            "unless" => Statement::Unless { condition, body, span },
+            "while" => Statement::While { condition, body, span },
+            _ => Statement::Until { condition, body, span },
+        })
+    }
+
+    fn forStatement(&mut self) -> Parsed<Statement> {
+        let start = self.pos;
+        self.advance();
+        let open = self.expect(Token::OpenParen, "`(`")?;
+
+        // `for (init; condition; step)` has two `;`s before its `)`,
+        // `for (condition)` has none
+        let mut depth: usize = 0;
+        let mut semicolons: usize = 0;
+        for (token, _, _) in &self.tokens[self.pos..] {
+            match token {
+                Token::OpenParen | Token::OpenBrace => depth += 1,
+                Token::CloseParen | Token::CloseBrace if depth == 0 => break,
+                Token::CloseParen | Token::CloseBrace => depth -= 1,
+                Token::Semicolon if depth == 0 => semicolons += 1,
+                _ => {}
+            }
+        }
+
+        let (init, condition, step) = match semicolons {
+            0 => (None, Some(self.expression()?), None),
+            2 => {
+                let init = match self.peek() {
+                    Some(Token::Semicolon) => None,
+                    Some(Token::Var | Token::Const | Token::Sink) => Some(Box::new(self.varDec(None)?)),
+                    _ => Some(Box::new(self.simple()?)),
+                };
+                self.expect(Token::Semicolon, "`;`")?;
+                let condition = if self.check(&Token::Semicolon) { None } else { Some(self.expression()?) };
+                self.expect(Token::Semicolon, "`;`")?;
+                let step = if self.check(&Token::CloseParen) { None } else { Some(Box::new(self.simple()?)) };
+                (init, condition, step)
             }
+            _ => {
+                return Err(JadeError::Parse(String::from("Expected `for (init; condition; step)` or `for (condition)`").into())
+                    .at(open.2));
+            }
+        };
+        self.expect(Token::CloseParen, "`)`")?;
+        let body = self.block()?;
+
+        Ok(Statement::For { init, condition, step, body, span: self.spanFrom(start) })
+    }
+
+    // Assignments, prints and calls, the statements that don't start
+    // with a keyword
+    fn simple(&mut self) -> Parsed<Statement> {
+        match self.assignment()? {
+            Some(statement) => Ok(statement),
+            None => self.output(),
+        }
+    }
+
+    // `name = value`, `name.field.field = value` or `name << source`.
+    // Nothing is used up unless it is one, `name.field ->` is a print.
+    fn assignment(&mut self) -> Parsed<Option<Statement>> {
+        let start = self.pos;
+        if !self.check(&Token::Name) {
+            return Ok(None);
+        }
+        let name = self.advance().1;
+        let mut path: Vec<String> = Vec::new();
+        while self.check(&Token::Dot) && self.peekAt(1) == Some(&Token::Name) {
+            self.advance();
+            path.push(self.advance().1);
+        }
+        if path.is_empty() && self.eat(&Token::AppendR) {
+            let source = match self.peek() {
+                Some(Token::Semicolon | Token::CloseParen) | None => None,
+                _ => Some(self.expression()?),
+            };
+            return Ok(Some(Statement::Read { name, source, slot: None, span: self.spanFrom(start) }));
+        }
+        if !self.eat(&Token::Equals) {
+            self.pos = start;
+            return Ok(None);
+        }
+
+        let value = self.expression()?;
+        let span = self.spanFrom(start);
+        Ok(Some(if path.is_empty() {
+            Statement::VarSet { name, value, slot: None, span }
+        } else {
+            Statement::FieldSet { name, path, value, slot: None, span }
+        }))
+    }
+
+    // `value, value -> target`, where the target is left out to print,
+    // or `value >> target`. A call on its own is run for what it does.
+    fn output(&mut self) -> Parsed<Statement> {
+        let start = self.pos;
+        let mut values: Vec<Expression> = vec![self.expression()?];
+        if self.eat(&Token::AppendW) {
+            let target = self.expression()?;
+            return Ok(Statement::Append { value: values.remove(0), target, span: self.spanFrom(start) });
+        }
+        while self.eat(&Token::Comma) {
+            values.push(self.expression()?);
+        }
+
+        if !self.eat(&Token::Print) {
+            return match values.pop() {
+                Some(value @ Expression { kind: ExpressionKind::Call { .. }, .. }) if values.is_empty() => {
+                    Ok(Statement::Expression { value, span: self.spanFrom(start) })
+                }
+                Some(Expression { kind: ExpressionKind::Variable { .. }, .. }) if values.is_empty() => {
+                    Err(self.unexpected("`=`, `->`, `>>`, `<<` or `.`"))
+                }
+                Some(_) if values.is_empty() => Err(self.unexpected("`,`, `->` or `>>`")),
+                _ => Err(self.unexpected("`,` or `->`")),
+            };
+        }
+
+        let target = match self.peek() {
+            Some(Token::Semicolon | Token::CloseParen) | None => None,
+            _ => Some(self.expression()?),
+        };
+        Ok(Statement::Print { values, target, span: self.spanFrom(start) })
+    }
+
+    // EXPRESSIONS
+
+    fn expression(&mut self) -> Parsed<Expression> {
+        self.binary(0)
+    }
+
+    // Precedence climbing over the levels in BINARY_LEVELS, loosest
+    // first. `**` and `^/` group to the right, the rest to the left.
+    fn binary(&mut self, level: usize) -> Parsed<Expression> {
+        if level == BINARY_LEVELS.len() {
+            return self.postfix();
+        }
+        let start = self.pos;
+        let left = self.binary(level + 1)?;
+        let Some(op) = self.peek().and_then(|t| binaryOperator(t, level)) else {
+            return Ok(left);
+        };
+
+        if level == BINARY_LEVELS.len() - 1 {
+            self.advance();
+            let right = self.binary(level)?;
+            return Ok(Expression {
+                kind: ExpressionKind::BinaryOp { op, left: Box::new(left), right: Box::new(right) },
+                span: self.spanFrom(start),
+            });
         }
-        // Set the left and right expressions based on the position
-        // of the operator
-        
-        let mut left = expressionTokens[0..opIdx].to_vec();
-        //print!("Left: {:#?}\n", left);
-        if left.get(0) == Some(&(Token::OpenParen, String::from("("))) {
-            left.remove(0);
-            left.remove(left.len()-1);
+
+        let mut expression = left;
+        let mut op = Some(op);
+        while let Some(current) = op {
+            self.advance();
+            let right = self.binary(level + 1)?;
+            expression = Expression {
+                kind: ExpressionKind::BinaryOp { op: current, left: Box::new(expression), right: Box::new(right) },
+                span: self.spanFrom(start),
+            };
+            op = self.peek().and_then(|t| binaryOperator(t, level));
         }
-        
+        Ok(expression)
+    }
 
-        let mut right = expressionTokens[opIdx + 1..expLen].to_vec();
-        //print!("Right: {:#?}\n", right);
-        if right.get(0) == Some(&(Token::OpenParen, String::from("("))) {
-            right.remove(0);
-            right.remove(right.len()-1);
+    // `object.field.field`, `?.` skips the rest when the object is null
+    fn postfix(&mut self) -> Parsed<Expression> {
+        let start = self.pos;
+        let mut expression = self.primary()?;
+        while let Some(dot @ (Token::Dot | Token::OptionalDot)) = self.peek().cloned() {
+            self.advance();
+            let field = self.expectName("a field name")?;
+            expression = Expression {
+                kind: ExpressionKind::FieldAccess {
+                    object: Box::new(expression),
+                    field,
+                    optional: dot == Token::OptionalDot,
+                },
+                span: self.spanFrom(start),
+            };
         }
+        Ok(expression)
+    }
+
+    fn primary(&mut self) -> Parsed<Expression> {
+        let start = self.pos;
+        let kind = match (self.peek(), self.peekAt(1)) {
+            // `Name { field = value, ... }`
+            (Some(Token::Name), Some(Token::OpenBrace)) => {
+                let name = self.advance().1;
+                self.advance();
+                let mut fields: Vec<(String, Expression)> = Vec::new();
+                while !self.check(&Token::CloseBrace) {
+                    let field = self.expectName("a field name or `}`")?;
+                    self.expect(Token::Equals, "`=`")?;
+                    fields.push((field, self.expression()?));
+                    if !self.eat(&Token::Comma) {
+                        break;
+                    }
+                }
+                self.expect(Token::CloseBrace, "`,` or `}`")?;
+                ExpressionKind::StructLiteral { name, fields }
+            }
+
+            // `Enum::Variant(payload, ...)`
+            (Some(Token::Name), Some(Token::DoubleColon)) => {
+                let enumName = self.advance().1;
+                self.advance();
+                let variant = self.expectName(&format!("a variant after `{}::`", enumName))?;
+                let mut payload: Vec<Expression> = Vec::new();
+                if self.eat(&Token::OpenParen) {
+                    while !self.check(&Token::CloseParen) {
+                        payload.push(self.expression()?);
+                        if !self.eat(&Token::Comma) {
+                            break;
+                        }
+                    }
+                    self.expect(Token::CloseParen, "`,` or `)`")?;
+                }
+                ExpressionKind::EnumVariant { enumName, variant, payload }
+            }
 
-        let leftExpression = evaluateExpression(left);
-        let rightExpression = evaluateExpression(right);
+            // `match (subject) { pattern => expression, ... }`
+            (Some(Token::ControlBlock), _) if self.checkKeyword("match") => {
+                self.advance();
+                let subject = self.condition()?;
+                self.expect(Token::OpenBrace, "`{` after the match subject")?;
+                let mut arms: Vec<(Pattern, Expression)> = Vec::new();
+                while !self.check(&Token::CloseBrace) {
+                    let pattern = self.pattern()?;
+                    self.expect(Token::FatArrow, "`=>`")?;
+                    arms.push((pattern, self.expression()?));
+                    if !self.eat(&Token::Comma) {
+                        break;
+                    }
+                }
+                self.expect(Token::CloseBrace, "`,` or `}`")?;
+                ExpressionKind::Match { subject: Box::new(subject), arms }
+            }
 
-        expression = Expression::BinaryOp {
-            op: op,
-            left: Box::new(leftExpression),
-            right: Box::new(rightExpression),
+            // `function(arg, ...)`. A name that isn't a function parses
+            // too, so the checker can say it doesn't exist.
+            (Some(Token::BuiltIn), _) | (Some(Token::Name), Some(Token::OpenParen)) => {
+                let name = self.advance().1;
+                self.expect(Token::OpenParen, &format!("`(` after `{}`", name))?;
+                let mut args: Vec<Expression> = Vec::new();
+                while !self.check(&Token::CloseParen) {
+                    args.push(self.expression()?);
+                    if !self.eat(&Token::Comma) {
+                        break;
+                    }
+                }
+                self.expect(Token::CloseParen, "`,` or `)`")?;
+                ExpressionKind::Call { name, args }
+            }
+
+            // `( expression )`
+            (Some(Token::OpenParen), _) => {
+                self.advance();
+                let inner = self.expression()?;
+                self.expect(Token::CloseParen, "`)`")?;
+                return Ok(Expression { kind: inner.kind, span: self.spanFrom(start) });
+            }
+
+            (Some(Token::Name | Token::String | Token::Number | Token::Bool | Token::Null), _) => {
+                return self.literal();
+            }
+
+            _ => return Err(self.unexpected("an expression")),
         };
+
+        Ok(Expression { kind, span: self.spanFrom(start) })
+    }
+
+    // A single name, number, string, bool or null
+    fn literal(&mut self) -> Parsed<Expression> {
+        let (token, text, span) = self.advance();
+        let kind = match token {
+            Token::Name => ExpressionKind::Variable { name: text, slot: None },
+            // The value is what is between the quotes
+            Token::String => ExpressionKind::String(text[1..text.len() - 1].to_string()),
+            Token::Number => ExpressionKind::Number(parseNumber(&text).map_err(|e| e.at(span.clone()))?),
+            Token::Bool => ExpressionKind::Boolean(text.to_lowercase() == "true"),
+            Token::Null => ExpressionKind::Null,
+            _ => {
+                self.pos -= 1;
+                return Err(self.unexpected("a value"));
+            }
+        };
+        Ok(Expression { kind, span })
+    }
+
+    fn pattern(&mut self) -> Parsed<Pattern> {
+        match (self.peek(), self.peekAt(1)) {
+            (Some(Token::Name), Some(Token::DoubleColon)) => {
+                let enumName = self.advance().1;
+                self.advance();
+                let variant = self.expectName(&format!("a variant after `{}::`", enumName))?;
+                let mut payload: Vec<Pattern> = Vec::new();
+                if self.eat(&Token::OpenParen) {
+                    while !self.check(&Token::CloseParen) {
+                        payload.push(self.pattern()?);
+                        if !self.eat(&Token::Comma) {
+                            break;
+                        }
+                    }
+                    self.expect(Token::CloseParen, "`,` or `)`")?;
+                }
+                Ok(Pattern::Variant { enumName, variant, payload })
+            }
+            (Some(Token::Name), _) => {
+                let name = self.advance().1;
+                Ok(if name == "_" { Pattern::Wildcard } else { Pattern::Binding(name) })
+            }
+            (Some(Token::String | Token::Number | Token::Bool | Token::Null), _) => {
+                let start = self.literal()?;
+                let inclusive = match self.peek() {
+                    Some(Token::Range) => false,
+                    Some(Token::RangeInclusive) => true,
+                    _ => return Ok(Pattern::Literal(start)),
+                };
+                self.advance();
+                let end = self.literal()?;
+                Ok(Pattern::Range { start, end, inclusive })
+            }
+            _ => Err(self.unexpected("a pattern")),
+        }
     }
-    expression
 }
 
-fn evalToken(token: (Token, String)) -> Expression {
-    match token.0 {
-        Token::Name => Expression::Variable(token.1),
-        Token::String => Expression::String(token.1),
-        Token::Number => {
-            if token.1.contains(".") {
-                Expression::Float(token.1.parse::<f64>().unwrap())
-            } else {
-                Expression::Integer(token.1.parse::<i64>().unwrap())
+/// Binary operators from loosest to tightest binding.
+const BINARY_LEVELS: &[&[(Token, BinaryOperator)]] = &[
+    &[(Token::NullCoalesce, BinaryOperator::NullCoalesce)],
+    &[
+        (Token::Or, BinaryOperator::Or),
+        (Token::Xor, BinaryOperator::Xor),
+        (Token::Nor, BinaryOperator::Nor),
+        (Token::Xnor, BinaryOperator::Xnor),
+    ],
+    &[(Token::And, BinaryOperator::And), (Token::Nand, BinaryOperator::Nand)],
+    &[
+        (Token::Equality, BinaryOperator::Equal),
+        (Token::NotEqual, BinaryOperator::NotEqual),
+        (Token::TrueEquality, BinaryOperator::TrueEquality),
+    ],
+    &[
+        (Token::Less, BinaryOperator::LessThan),
+        (Token::Greater, BinaryOperator::GreaterThan),
+        (Token::LessOEqual, BinaryOperator::LessOrEqual),
+        (Token::GreaterOEqual, BinaryOperator::GreaterOrEqual),
+    ],
+    &[(Token::Addition, BinaryOperator::Add), (Token::Subtraction, BinaryOperator::Subtract)],
+    &[
+        (Token::Multiplication, BinaryOperator::Multiply),
+        (Token::Division, BinaryOperator::Divide),
+        (Token::Modulo, BinaryOperator::Modulo),
+    ],
+    &[(Token::Exponent, BinaryOperator::Exponent), (Token::Root, BinaryOperator::Root)],
+];
+
+// The operator `token` stands for at precedence `level`, if any
+fn binaryOperator(token: &Token, level: usize) -> Option<BinaryOperator> {
+    BINARY_LEVELS[level].iter().find(|(t, _)| t == token).map(|(_, op)| op.clone())
+}
+
+/// Checks that every `match` in the code covers all of its cases.
+/// Enum matches need every variant (or a `_`), everything else needs
+/// a catch-all arm unless it is a bool with both `true` and `false`.
+pub fn checkMatches(code: &[Statement]) -> Result<(), Vec<JadeError>> {
+    checkMatchesAfter(code, &HashMap::new())
+}
+
+/// Checks the matches in code that runs after the enums in `known` were
+/// declared, like the ones an earlier eval left in the runtime.
+pub fn checkMatchesAfter(code: &[Statement], known: &HashMap<String, Vec<(String, Vec<String>)>>) -> Result<(), Vec<JadeError>> {
+    let mut enums = known.clone();
+    collectEnums(code, &mut enums);
+    let mut errors: Vec<JadeError> = Vec::new();
+    for statement in code {
+        checkStatementMatches(statement, &enums, &mut errors);
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn collectEnums<'a>(code: impl IntoIterator<Item = &'a Statement>, enums: &mut HashMap<String, Vec<(String, Vec<String>)>>) {
+    for statement in code {
+        if let Statement::EnumDec { name, variants, .. } = statement {
+            enums.insert(name.clone(), variants.clone());
+        }
+        collectEnums(statement.children(), enums);
+    }
+}
+
+fn checkStatementMatches(statement: &Statement, enums: &HashMap<String, Vec<(String, Vec<String>)>>, errors: &mut Vec<JadeError>) {
+    for expr in statement.expressions() {
+        checkExpressionMatches(expr, enums, errors);
+    }
+    for inner in statement.children() {
+        checkStatementMatches(inner, enums, errors);
+    }
+}
+
+fn checkExpressionMatches(expr: &Expression, enums: &HashMap<String, Vec<(String, Vec<String>)>>, errors: &mut Vec<JadeError>) {
+    match &expr.kind {
+        ExpressionKind::BinaryOp { left, right, .. } => {
+            checkExpressionMatches(left, enums, errors);
+            checkExpressionMatches(right, enums, errors);
+        }
+        ExpressionKind::StructLiteral { fields, .. } => {
+            for (_, field) in fields {
+                checkExpressionMatches(field, enums, errors);
             }
         }
-        Token::Bool => {
-            let val = token.1.to_lowercase();
-            Expression::Boolean(val == "true" || val == "1")
+        ExpressionKind::FieldAccess { object, .. } => checkExpressionMatches(object, enums, errors),
+        ExpressionKind::EnumVariant { payload: values, .. } |
+        ExpressionKind::Call { args: values, .. } => {
+            for value in values {
+                checkExpressionMatches(value, enums, errors);
+            }
+        }
+        ExpressionKind::Match { subject, arms } => {
+            checkExpressionMatches(subject, enums, errors);
+            for (_, arm) in arms {
+                checkExpressionMatches(arm, enums, errors);
+            }
+            if let Err(error) = checkArms(arms, enums) {
+                errors.push(error.at(expr.span.clone()));
+            }
         }
-        _ => {
-            parsingError();
-            Expression::Float(0.0)
+        _ => {}
+    }
+}
+
+fn checkArms(arms: &[(Pattern, Expression)], enums: &HashMap<String, Vec<(String, Vec<String>)>>) -> Result<(), JadeError> {
+    let patterns: Vec<&Pattern> = arms.iter().map(|(p, _)| p).collect();
+    if patterns.iter().any(|p| p.isIrrefutable()) {
+        return Ok(());
+    }
+
+    // Enum match, every variant needs an arm that takes any payload
+    let enumName = patterns.iter().find_map(|p| match p {
+        Pattern::Variant { enumName, .. } => Some(enumName),
+        _ => None,
+    });
+    if let Some(enumName) = enumName {
+        // The type checker reports enums that don't exist
+        let Some(variants) = enums.get(enumName) else { return Ok(()) };
+        let missing: Vec<String> = variants
+            .iter()
+            .filter(|(v, types)| !patterns.iter().any(|p| matches!(p,
+                Pattern::Variant { enumName: e, variant, payload }
+                    if e == enumName && variant == v && payload.len() == types.len()
+                        && payload.iter().all(|p| p.isIrrefutable()))))
+            .map(|(v, _)| format!("{}::{}", enumName, v))
+            .collect();
+        if !missing.is_empty() {
+            return Err(nonExhaustiveMatchError(&missing.join(", ")));
+        }
+        return Ok(());
+    }
+
+    // Bool match with both values
+    let hasBool = |b: bool| patterns.iter().any(|p| matches!(p, Pattern::Literal(Expression { kind: ExpressionKind::Boolean(v), .. }) if *v == b));
+    if hasBool(true) && hasBool(false) {
+        return Ok(());
+    }
+
+    Err(nonExhaustiveMatchError("_"))
+}
+
+/// Turns a number literal into its value. Handles `0x`, `0b` and `0o`
+/// prefixes, `_` separators, exponents and width suffixes like `u8` or
+/// `f32`. Without a suffix integers are `int64` (or `uint64` if they
+/// only fit there) and floats are `float64`.
+fn parseNumber(literal: &str) -> Result<Value, JadeError> {
+    let text: String = literal.chars().filter(|c| *c != '_').collect();
+
+    let radix = match text.get(..2) {
+        Some("0x" | "0X") => 16,
+        Some("0b" | "0B") => 2,
+        Some("0o" | "0O") => 8,
+        _ => 10,
+    };
+
+    let suffix = ["u8", "u16", "u32", "u64", "i8", "i16", "i32", "i64", "f32", "f64"]
+        .into_iter()
+        .find(|suffix| text.ends_with(suffix))
+        // `f32` on the end of a hex number is just digits
+        .filter(|suffix| !(suffix.starts_with('f') && radix == 16));
+    let digits = &text[..text.len() - suffix.map_or(0, str::len)];
+    let isFloat = radix == 10 && digits.contains(['.', 'e', 'E']);
+
+    // Floats, or integers with a float suffix
+    if isFloat || suffix.is_some_and(|s| s.starts_with('f')) {
+        if radix != 10 {
+            return Err(JadeError::Parse(format!("`{}` can't have a float suffix", literal).into()));
         }
+        let value: f64 = digits.parse().map_err(|_| JadeError::Parse(format!("Invalid number `{}`", literal).into()))?;
+        return match suffix {
+            Some("f32") => {
+                let narrowed = value as f32;
+                if narrowed.is_finite() { Ok(Value::Float32(narrowed)) } else { Err(literalRangeError(literal, "float32")) }
+            }
+            None | Some("f64") if value.is_finite() => Ok(Value::Float64(value)),
+            None | Some("f64") => Err(literalRangeError(literal, "float64")),
+            Some(_) => Err(JadeError::Parse(format!("`{}` is a float, it can't have an integer suffix", literal).into())),
+        };
+    }
+
+    let start = if radix == 10 { 0 } else { 2 };
+    let value = u128::from_str_radix(&digits[start..], radix)
+        .map_err(|_| JadeError::Parse(format!("Invalid number `{}`", literal).into()))?;
+
+    // Narrow to the suffix's width, or the smallest of int64/uint64 it fits
+    let typeName = match suffix {
+        Some(s) if s.starts_with('u') => format!("uint{}", &s[1..]),
+        Some(s) => format!("int{}", &s[1..]),
+        None => String::from("int64"),
+    };
+    let tooBig = || literalRangeError(literal, &typeName);
+    match suffix {
+        Some("u8") => u8::try_from(value).map(Value::UInt8).map_err(|_| tooBig()),
+        Some("u16") => u16::try_from(value).map(Value::UInt16).map_err(|_| tooBig()),
+        Some("u32") => u32::try_from(value).map(Value::UInt32).map_err(|_| tooBig()),
+        Some("u64") => u64::try_from(value).map(Value::UInt64).map_err(|_| tooBig()),
+        Some("i8") => i8::try_from(value).map(Value::Int8).map_err(|_| tooBig()),
+        Some("i16") => i16::try_from(value).map(Value::Int16).map_err(|_| tooBig()),
+        Some("i32") => i32::try_from(value).map(Value::Int32).map_err(|_| tooBig()),
+        Some("i64") => i64::try_from(value).map(Value::Int64).map_err(|_| tooBig()),
+        _ => i64::try_from(value)
+            .map(Value::Int64)
+            .or_else(|_| u64::try_from(value).map(Value::UInt64))
+            .map_err(|_| literalRangeError(literal, "uint64")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lexCode;
+
+    fn parse(code: &str) -> Result<Vec<Statement>, Vec<JadeError>> {
+        parseCode(lexCode(code.to_string(), "test.bg").unwrap())
+    }
+
+    #[test]
+    fn reportsEveryBadStatement() {
+        let errors = parse("var a int = ;\nvar b int = 2;\nb = 1 +;\nvar c int = 3;").unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].diagnostic().span.as_ref().unwrap().line, 1);
+        assert_eq!(errors[1].diagnostic().span.as_ref().unwrap().line, 3);
+    }
+
+    #[test]
+    fn recoversInsideBlocks() {
+        let errors = parse("if (true) {\n  a = ;\n  b = ;\n}\nc = ;").unwrap_err();
+        assert_eq!(errors.len(), 3);
+    }
+
+    #[test]
+    fn strayBraceIsOneError() {
+        let errors = parse("}\nvar a int = 1;").unwrap_err();
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn bindsTighterOperatorsFirst() {
+        let program = parse("var a int = 1 + 2 * 3;").unwrap();
+        let Statement::VarDec { value, .. } = &program[0] else { panic!("not a declaration") };
+        assert!(matches!(&value.kind, ExpressionKind::BinaryOp { op: BinaryOperator::Add, .. }));
+    }
+
+    #[test]
+    fn typeIsOptional() {
+        let program = parse("var a = 5;
+const b string? = null;").unwrap();
+        let Statement::VarDec { datatype, value, .. } = &program[0] else { panic!("not a declaration") };
+        assert_eq!(*datatype, None);
+        assert!(matches!(value.kind, ExpressionKind::Number(Value::Int64(5))));
+        let Statement::VarDec { datatype, .. } = &program[1] else { panic!("not a declaration") };
+        assert_eq!(datatype.as_deref(), Some("string?"));
+    }
+
+    #[test]
+    fn parsesCalls() {
+        let program = parse("print(1 + 2, \"x\");\nvar a = print();").unwrap();
+        let Statement::Expression { value, .. } = &program[0] else { panic!("not a call statement") };
+        let ExpressionKind::Call { name, args } = &value.kind else { panic!("not a call") };
+        assert_eq!((name.as_str(), args.len()), ("print", 2));
+        let Statement::VarDec { value, .. } = &program[1] else { panic!("not a declaration") };
+        assert!(matches!(&value.kind, ExpressionKind::Call { args, .. } if args.is_empty()));
+        assert!(parse("print 1;").is_err());
+    }
+
+    #[test]
+    fn parsesPrints() {
+        let program = parse("(a + 1) ->;\na, p.x, \"s\" -> \"out.txt\";\np.x = 1;").unwrap();
+        let Statement::Print { values, target: None, .. } = &program[0] else { panic!("not a print") };
+        assert!(matches!(&values[..], [Expression { kind: ExpressionKind::BinaryOp { .. }, .. }]));
+        let Statement::Print { values, target: Some(target), .. } = &program[1] else { panic!("not a write") };
+        assert_eq!(values.len(), 3);
+        assert_eq!(target.kind, ExpressionKind::String("out.txt".into()));
+        assert!(matches!(&program[2], Statement::FieldSet { .. }));
+        assert!(parse("a, b;").is_err());
+        assert!(parse("1 + 1;").is_err());
+    }
+
+    #[test]
+    fn parsesAppendsAndReads() {
+        let program = parse("sink log = \"a.log\";\np.x + 1 >> log;\nline <<;\ntext << \"in.txt\";").unwrap();
+        assert!(matches!(&program[0], Statement::VarDec { kind: DeclKind::Sink, datatype: Some(t), .. } if t == "sink"));
+        let Statement::Append { value, target, .. } = &program[1] else { panic!("not an append") };
+        assert!(matches!(value.kind, ExpressionKind::BinaryOp { .. }));
+        assert!(matches!(&target.kind, ExpressionKind::Variable { name, .. } if name == "log"));
+        assert!(matches!(&program[2], Statement::Read { name, source: None, .. } if name == "line"));
+        assert!(matches!(&program[3], Statement::Read { source: Some(_), .. }));
+        assert!(parse("sink log string = \"a.log\";").is_err());
+        assert!(parse("a, b >> c;").is_err());
+    }
+
+    #[test]
+    fn hexDigitsAreNotAFloatSuffix() {
+        assert_eq!(parseNumber("0xAf32").unwrap(), Value::Int64(0xAF32));
+        assert_eq!(parseNumber("0XAf32").unwrap(), Value::Int64(0xAF32));
+        assert_eq!(parseNumber("0XFFu8").unwrap(), Value::UInt8(255));
+        assert!(parseNumber("0B1f32").is_err());
+    }
+
+    #[test]
+    fn parsesIfElifElse() {
+        let program = parse("if (a) { x = 1; } elif (b) { x = 2; } else { x = 3; x = 4; }").unwrap();
+        let Statement::If { body, otherwise: Some(otherwise), .. } = &program[0] else { panic!("not an if") };
+        assert_eq!(body.len(), 1);
+        let [Statement::If { condition, otherwise: Some(last), .. }] = &otherwise[..] else { panic!("not an elif") };
+        assert!(matches!(&condition.kind, ExpressionKind::Variable { name, .. } if name == "b"));
+        assert_eq!(last.len(), 2);
+        let Statement::If { otherwise, .. } = &parse("if (a) { x = 1; }").unwrap()[0] else { panic!("not an if") };
+        assert_eq!(*otherwise, None);
+    }
+
+    #[test]
+    fn forPartsAreOptional() {
+        let program = parse("for (;;) { x = 1; }\nfor (var i = 0; i < 3; i = i + 1) {}").unwrap();
+        let Statement::For { init: None, condition: None, step: None, body, .. } = &program[0] else { panic!("not a bare for") };
+        assert_eq!(body.len(), 1);
+        let Statement::For { init: Some(init), condition: Some(_), step: Some(step), .. } = &program[1] else { panic!("not a full for") };
+        assert!(matches!(**init, Statement::VarDec { .. }));
+        assert!(matches!(**step, Statement::VarSet { .. }));
+    }
+
+    #[test]
+    fn parsesStructLiterals() {
+        let program = parse("var p = Point { x = 1, y = a + 2 };").unwrap();
+        let Statement::VarDec { value, .. } = &program[0] else { panic!("not a declaration") };
+        let ExpressionKind::StructLiteral { name, fields } = &value.kind else { panic!("not a struct literal") };
+        assert_eq!(name, "Point");
+        let names: Vec<&str> = fields.iter().map(|(field, _)| field.as_str()).collect();
+        assert_eq!(names, ["x", "y"]);
+        assert!(matches!(fields[1].1.kind, ExpressionKind::BinaryOp { .. }));
+    }
+
+    #[test]
+    fn strayBraceDoesNotEatTheNextStatement() {
+        let (program, errors) = parseRecovering(lexCode("}\nvar a int = 1;".to_string(), "test.bg").unwrap());
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(&program[..], [Statement::VarDec { name, .. }] if name == "a"));
+    }
+
+    #[test]
+    fn variantArmsNeedTheWholePayload() {
+        let program = parse("enum C { A(int), B }\nvar c = C::B;\nvar n = match (c) { C::A => 1, C::B => 2 };").unwrap();
+        let errors = checkMatches(&program).unwrap_err();
+        assert_eq!(errors[0].diagnostic().message, nonExhaustiveMatchError("C::A").diagnostic().message);
+        let program = parse("enum C { A(int), B }\nvar c = C::B;\nvar n = match (c) { C::A(x) => x, C::B => 2 };").unwrap();
+        assert!(checkMatches(&program).is_ok());
     }
 }
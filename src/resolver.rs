@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+
+use crate::lexer::Span;
+use crate::jadeErrors::{suggest, variableNotFoundError, JadeError};
+use crate::parser::{Expression, ExpressionKind, Pattern, Slot, Statement};
+
+/// Gives every variable in the program the slot it lives in, so the
+/// runtime never has to look a name up. `globals` is the name of each
+/// global slot, new names are added to the end. Names that are never
+/// declared are reported here, before anything runs.
+pub fn resolve(code: &mut [Statement], globals: &mut Vec<String>) -> Result<(), Vec<JadeError>> {
+    let mut resolver = Resolver {
+        indexes: globals.iter().enumerate().map(|(index, name)| (name.clone(), index)).collect(),
+        globals,
+        scopes: Vec::new(),
+        errors: Vec::new(),
+    };
+    resolver.statements(code);
+
+    if resolver.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(resolver.errors)
+    }
+}
+
+struct Resolver<'a> {
+    globals: &'a mut Vec<String>,
+    indexes: HashMap<String, usize>,        // The slot of each global name
+    scopes: Vec<HashMap<String, usize>>,    // `match` bindings, innermost last
+    errors: Vec<JadeError>,
+}
+
+impl Resolver<'_> {
+    fn statements(&mut self, code: &mut [Statement]) {
+        for statement in code {
+            self.statement(statement);
+        }
+    }
+
+    // Statements are walked in the order they run, so a name is only
+    // known after its declaration
+    fn statement(&mut self, statement: &mut Statement) {
+        match statement {
+            Statement::VarDec { name, value, slot, .. } => {
+                self.expression(value);
+                *slot = Some(self.declare(name));
+            }
+
+            Statement::VarSet { name, value, slot, span } |
+            Statement::FieldSet { name, value, slot, span, .. } => {
+                *slot = self.global(name, span);
+                self.expression(value);
+            }
+
+            Statement::Print { values, target, .. } => {
+                for value in values.iter_mut().chain(target) {
+                    self.expression(value);
+                }
+            }
+
+            Statement::Append { value, target, .. } => {
+                self.expression(value);
+                self.expression(target);
+            }
+
+            Statement::Read { name, source, slot, span } => {
+                *slot = self.global(name, span);
+                if let Some(source) = source {
+                    self.expression(source);
+                }
+            }
+
+            Statement::Expression { value, .. } => self.expression(value),
+
+            Statement::StructDec { .. } | Statement::EnumDec { .. } => {}
+
+            Statement::If { condition, body, otherwise, .. } => {
+                self.expression(condition);
+                self.statements(body);
+                if let Some(otherwise) = otherwise {
+                    self.statements(otherwise);
+                }
+            }
+
+            Statement::Unless { condition, body, .. } |
+            Statement::While { condition, body, .. } |
+            Statement::Until { condition, body, .. } => {
+                self.expression(condition);
+                self.statements(body);
+            }
+
+            Statement::For { init, condition, step, body, .. } => {
+                if let Some(init) = init {
+                    self.statement(init);
+                }
+                if let Some(condition) = condition {
+                    self.expression(condition);
+                }
+                self.statements(body);
+                if let Some(step) = step {
+                    self.statement(step);
+                }
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &mut Expression) {
+        match &mut expression.kind {
+            ExpressionKind::Number(_) | ExpressionKind::String(_) | ExpressionKind::Boolean(_) | ExpressionKind::Null => {}
+
+            ExpressionKind::Variable { name, slot } => {
+                *slot = self.variable(name);
+                if slot.is_none() {
+                    self.errors.push(self.notFound(name).at(expression.span.clone()));
+                }
+            }
+
+            ExpressionKind::BinaryOp { left, right, .. } => {
+                self.expression(left);
+                self.expression(right);
+            }
+
+            ExpressionKind::StructLiteral { fields, .. } => {
+                for (_, value) in fields {
+                    self.expression(value);
+                }
+            }
+
+            ExpressionKind::FieldAccess { object, .. } => self.expression(object),
+
+            ExpressionKind::EnumVariant { payload: values, .. } |
+            ExpressionKind::Call { args: values, .. } => {
+                for value in values {
+                    self.expression(value);
+                }
+            }
+
+            // Each arm gets its own scope, its bindings take the indexes
+            // in the order the pattern binds them
+            ExpressionKind::Match { subject, arms } => {
+                self.expression(subject);
+                for (pattern, arm) in arms {
+                    self.pattern(pattern);
+                    let names = pattern.bindings().into_iter().cloned();
+                    let scope = names.enumerate().map(|(index, name)| (name, index)).collect();
+                    self.scopes.push(scope);
+                    self.expression(arm);
+                    self.scopes.pop();
+                }
+            }
+        }
+    }
+
+    fn pattern(&mut self, pattern: &mut Pattern) {
+        match pattern {
+            Pattern::Literal(literal) => self.expression(literal),
+            Pattern::Range { start, end, .. } => {
+                self.expression(start);
+                self.expression(end);
+            }
+            Pattern::Variant { payload, .. } => {
+                for pattern in payload {
+                    self.pattern(pattern);
+                }
+            }
+            Pattern::Wildcard | Pattern::Binding(_) => {}
+        }
+    }
+
+    // The slot for a declaration, declaring the same name again reuses it
+    fn declare(&mut self, name: &str) -> usize {
+        if let Some(index) = self.indexes.get(name) {
+            return *index;
+        }
+        self.globals.push(name.to_string());
+        self.indexes.insert(name.to_string(), self.globals.len() - 1);
+        self.globals.len() - 1
+    }
+
+    // A variable, checking `match` bindings before the globals
+    fn variable(&self, name: &str) -> Option<Slot> {
+        let bound = self.scopes.iter().enumerate().rev().find_map(|(depth, scope)| {
+            scope.get(name).map(|index| Slot { depth: depth + 1, index: *index })
+        });
+        bound.or_else(|| self.indexes.get(name).map(|index| Slot { depth: 0, index: *index }))
+    }
+
+    // A global that a statement assigns to or prints
+    fn global(&mut self, name: &str, span: &Span) -> Option<usize> {
+        let slot = self.indexes.get(name).copied();
+        if slot.is_none() {
+            self.errors.push(self.notFound(name).at(span.clone()));
+        }
+        slot
+    }
+
+    fn notFound(&self, name: &str) -> JadeError {
+        let scoped = self.scopes.iter().flat_map(|scope| scope.keys());
+        suggest(variableNotFoundError(name), name, scoped.chain(self.globals.iter()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lexCode;
+    use crate::parser::parseCode;
+
+    fn resolved(code: &str) -> (Vec<Statement>, Result<(), Vec<JadeError>>, Vec<String>) {
+        let mut program = parseCode(lexCode(code.to_string(), "test.bg").unwrap()).unwrap();
+        let mut globals = Vec::new();
+        let result = resolve(&mut program, &mut globals);
+        (program, result, globals)
+    }
+
+    // The slot of the value in `name = value;`
+    fn slotOf(statement: &Statement) -> Option<Slot> {
+        match statement {
+            Statement::VarDec { value, .. } | Statement::VarSet { value, .. } => match &value.kind {
+                ExpressionKind::Variable { slot, .. } => *slot,
+                ExpressionKind::Match { arms, .. } => match &arms[0].1.kind {
+                    ExpressionKind::Variable { slot, .. } => *slot,
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn givesEveryNameASlot() {
+        let (program, result, globals) = resolved("var a = 1;\nvar b = a;\na = b;\nvar a = 3;");
+        assert_eq!(result, Ok(()));
+        assert_eq!(globals, vec!["a", "b"]);
+        assert_eq!(slotOf(&program[1]), Some(Slot { depth: 0, index: 0 }));
+        assert_eq!(slotOf(&program[2]), Some(Slot { depth: 0, index: 1 }));
+        assert!(matches!(program[3], Statement::VarDec { slot: Some(0), .. }));
+    }
+
+    #[test]
+    fn bindingsShadowGlobals() {
+        let code = "enum E { Pair(int, int) }\nvar x = 1;\nvar y = match (E::Pair(2, 3)) { E::Pair(a, x) => x };";
+        let (program, result, _) = resolved(code);
+        assert_eq!(result, Ok(()));
+        assert_eq!(slotOf(&program[2]), Some(Slot { depth: 1, index: 1 }));
+    }
+
+    #[test]
+    fn reportsUnknownNamesBeforeRunning() {
+        let (_, result, _) = resolved("var count = 1;\nvar a = cont + 1;\nb = 2;\nmissing ->;");
+        let errors = result.unwrap_err();
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[0].diagnostic().message, "Variable 'cont' not found");
+        assert_eq!(errors[0].diagnostic().hint.as_deref(), Some("did you mean `count`?"));
+        assert_eq!(errors[1].diagnostic().span.as_ref().map(|span| span.line), Some(3));
+    }
+}
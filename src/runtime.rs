@@ -1,9 +1,20 @@
-use crate::evaluator::evaluate;
-use std::{collections::HashMap};
-use crate::parser::ParsedBlock;
+use crate::evaluator::{coerce_to_type, evaluate};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::rc::Rc;
+use crate::lexer::openFile;
+use crate::parser::{Expression, ExpressionKind, Slot, Statement};
+use crate::resolver::resolve;
+use crate::jadeErrors::{
+    appendError, argumentCountError, fieldNotFoundError, functionNotFoundError, inferNullError, inputError, outputError,
+    suggest, valueError, variableNotFoundError, writeFileError, JadeError,
+};
 
 #[allow(dead_code)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Int8(i8),
     UInt8(u8),
@@ -28,83 +39,836 @@ pub enum Value {
     FixedArray(Vec<Value>, usize),      // For fixed-length arrays
     Slice(Box<[Value]>),                // Slice reference-like
     Pointer(Box<Value>),                // Simulated pointer
+    Map(HashMap<String, Value>),        // String keys, for values from host code
+    Struct(String, Vec<(String, Value)>), // Type name and fields in declared order
+    Enum(String, String, Vec<Value>),   // Enum name, variant and payload
 
     // Optional / Null
     Null,
 }
 
-#[derive(Default)]
+/// The Rust side of a function scripts can call.
+pub type FnBody = Box<dyn Fn(&[Value]) -> Result<Value, JadeError>>;
+
+/// A function registered from Rust. The arguments are checked against
+/// `params` before `body` sees them.
+pub struct NativeFn {
+    pub params: Vec<String>, // The type of each argument
+    pub body: FnBody,
+}
+
+/// The built-in functions and the type of each argument. A function
+/// registered with the same name takes over.
+const BUILT_INS: &[(&str, &[&str])] = &[("print", &["$"])];
+
 pub struct Runtime {
-    // A hashmap to store variables and their values
-    // The key is the variable name, and the value is a Value enum
-    // that can hold different types of data.
-    pub variables: HashMap<String, Box<(Value, String)>>,
+    // The globals by the slot the resolver gave them, each with its
+    // declared type. A slot is `None` until its declaration has run.
+    pub globals: Vec<Option<(Value, String)>>,
+
+    // The name of each global slot. The resolver adds to it, so names
+    // stay in the same slots from one program to the next.
+    pub names: Vec<String>,
+
+    // The declared structs. The key is the struct name and the value
+    // is the list of field names and types in declared order.
+    pub structs: HashMap<String, Vec<(String, String)>>,
+
+    // The declared enums. The key is the enum name and the value is the
+    // list of variants with their payload types.
+    pub enums: HashMap<String, Vec<(String, Vec<String>)>>,
+
+    // Values bound by `match` arms, innermost last, in the order the
+    // pattern binds them.
+    pub scopes: Vec<Vec<Value>>,
+
+    // The functions registered from Rust, by name
+    pub functions: HashMap<String, NativeFn>,
+
+    // Where everything the script prints goes, stdout unless changed
+    pub output: Box<dyn Write>,
+
+    // Where `name <<;` reads lines from, stdin unless changed
+    pub input: Box<dyn BufRead>,
+}
+
+impl Default for Runtime {
+    fn default() -> Self {
+        Runtime::new()
+    }
 }
+
 #[allow(dead_code)]
 impl Runtime {
     pub fn new() -> Self {
         Runtime {
-            variables: HashMap::new(),
+            globals: Vec::new(),
+            names: Vec::new(),
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            scopes: Vec::new(),
+            functions: HashMap::new(),
+            output: Box::new(std::io::stdout()),
+            input: Box::new(BufReader::new(std::io::stdin())),
+        }
+    }
+
+    // Read input from somewhere else
+    pub fn set_input(&mut self, input: impl BufRead + 'static) {
+        self.input = Box::new(input);
+    }
+
+    // Send what the script prints somewhere else
+    pub fn set_output(&mut self, output: impl Write + 'static) {
+        self.output = Box::new(output);
+    }
+
+    // Print a value on its own line
+    pub fn print(&mut self, value: &Value) -> Result<(), JadeError> {
+        writeln!(self.output, "{}", value).map_err(outputError)
+    }
+
+    // What `->` does: the values go on one line, to the output or over
+    // whatever is in the file named by `target`
+    pub fn print_to(&mut self, values: &[Value], target: Option<Value>) -> Result<(), JadeError> {
+        let line: Vec<String> = values.iter().map(Value::to_string).collect();
+        let line = line.join(" ");
+        match target {
+            None => writeln!(self.output, "{}", line).map_err(outputError),
+            Some(Value::String(fileName)) => {
+                std::fs::write(&fileName, line + "\n").map_err(|error| writeFileError(&fileName, error))
+            }
+            Some(other) => Err(valueError("string", other)),
+        }
+    }
+
+    // Add a function scripts can call, replacing any with the same name
+    pub fn register_fn(&mut self, name: &str, params: &[&str], body: impl Fn(&[Value]) -> Result<Value, JadeError> + 'static) {
+        let params = params.iter().map(|param| param.to_string()).collect();
+        self.functions.insert(name.to_string(), NativeFn { params, body: Box::new(body) });
+    }
+
+    // What `<<` reads: everything in the file named by `source`, or the
+    // next line of input without its newline, null once there are none
+    pub fn read_from(&mut self, source: Option<Value>) -> Result<Value, JadeError> {
+        match source {
+            Some(Value::String(fileName)) => Ok(Value::String(openFile(&fileName)?)),
+            Some(other) => Err(valueError("string", other)),
+            None => {
+                let mut line = String::new();
+                if self.input.read_line(&mut line).map_err(inputError)? == 0 {
+                    return Ok(Value::Null);
+                }
+                let end = line.trim_end_matches(['\n', '\r']).len();
+                line.truncate(end);
+                Ok(Value::String(line))
+            }
+        }
+    }
+
+    // `value >> name` for the global in a slot
+    pub fn append(&mut self, slot: usize, value: Value) -> Result<(), JadeError> {
+        let (current, declared) = self.declared_mut(slot)?;
+        appendTo(current, declared, value)
+    }
+
+    // Every function scripts can call and the type of each argument
+    pub fn signatures(&self) -> HashMap<String, Vec<String>> {
+        let builtIns = BUILT_INS
+            .iter()
+            .map(|(name, params)| (name.to_string(), params.iter().map(|p| p.to_string()).collect()));
+        let registered = self.functions.iter().map(|(name, function)| (name.clone(), function.params.clone()));
+        builtIns.chain(registered).collect()
+    }
+
+    // Call a function, after checking it gets the arguments it takes
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, JadeError> {
+        let signatures = self.signatures();
+        let params = signatures.get(name).ok_or_else(|| {
+            suggest(functionNotFoundError(name), name, signatures.keys())
+        })?;
+        if args.len() != params.len() {
+            return Err(argumentCountError(name, params.len(), args.len()));
+        }
+        let args = args
+            .into_iter()
+            .zip(params)
+            .map(|(arg, param)| coerce_to_type(arg, param))
+            .collect::<Result<Vec<Value>, JadeError>>()?;
+
+        match self.functions.get(name) {
+            Some(function) => (function.body)(&args),
+            None => self.callBuiltIn(name, &args),
+        }
+    }
+
+    // The built-ins need the runtime itself, for its output
+    fn callBuiltIn(&mut self, name: &str, args: &[Value]) -> Result<Value, JadeError> {
+        match name {
+            "print" => self.print(&args[0])?,
+            _ => return Err(functionNotFoundError(name)),
+        }
+        Ok(Value::Null)
+    }
+
+    // Add a new variable in a global slot
+    pub fn declare_variable(&mut self, slot: usize, value: Value, actualType: String) {
+        if self.globals.len() <= slot {
+            self.globals.resize(slot + 1, None);
         }
+        self.globals[slot] = Some((value, actualType));
+    }
+
+    // The slot of a global name, if it has one yet
+    pub fn slot_of(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n == name)
     }
 
-    // Add a new variable
-    pub fn declare_variable(&mut self, name: String, value: Value, actualType: String) {
-        println!("Declaring {} as {:?}", name, value);
-        self.variables.insert(name, Box::new((value, actualType)));
+    // The slot of a global name, giving it a new one if needed
+    pub fn slot_for(&mut self, name: &str) -> usize {
+        self.slot_of(name).unwrap_or_else(|| {
+            self.names.push(name.to_string());
+            self.names.len() - 1
+        })
     }
 
-    // Get the value of a variable
+    // Get the value of a global by name, for code outside the program
     pub fn get_variable(&self, name: &str) -> Option<&Value> {
-        Some(&self.variables.get(name).unwrap().0)
+        self.global(self.slot_of(name)?).map(|var| &var.0)
+    }
+
+    // A declared global and its type
+    pub fn global(&self, slot: usize) -> Option<&(Value, String)> {
+        self.globals.get(slot)?.as_ref()
+    }
+
+    // Get the value in a slot the resolver worked out
+    pub fn get_slot(&self, slot: Slot) -> Option<&Value> {
+        match slot.depth {
+            0 => self.global(slot.index).map(|var| &var.0),
+            depth => self.scopes.get(depth - 1)?.get(slot.index),
+        }
+    }
+
+    // Every global declared so far, used for "did you mean" hints
+    pub fn variable_names(&self) -> Vec<String> {
+        self.names
+            .iter()
+            .zip(&self.globals)
+            .filter(|(_, var)| var.is_some())
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    // Unknown variable, with a hint if something close is in scope
+    pub fn unknown_variable(&self, name: &str) -> JadeError {
+        suggest(variableNotFoundError(name), name, self.variable_names().iter())
+    }
+
+    // Start a new scope for `match` bindings
+    pub fn push_scope(&mut self) {
+        self.scopes.push(Vec::new());
+    }
+
+    // Throw away the innermost scope
+    pub fn pop_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    // Bind the next name in the innermost scope
+    pub fn bind_variable(&mut self, value: Value) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(value);
+        }
+    }
+
+    // The declared global in a slot, or the error for an unknown name
+    fn declared_mut(&mut self, slot: usize) -> Result<&mut (Value, String), JadeError> {
+        let name = &self.names[slot];
+        self.globals.get_mut(slot).and_then(Option::as_mut).ok_or_else(|| variableNotFoundError(name))
     }
 
     // Set the value of an existing variable
-    pub fn set_variable(&mut self, name: String, value: Value) {
-        if let Some(var) = self.variables.get_mut(&name) {
-            println!("Setting {} as {:?}", name, value);
-            var.0 = value;
+    pub fn set_variable(&mut self, slot: usize, value: Value) -> Result<(), JadeError> {
+        self.declared_mut(slot)?.0 = value;
+        Ok(())
+    }
+
+    // Set a field of a struct variable, `path` is the chain of fields
+    pub fn set_field(&mut self, slot: usize, path: &[String], value: Value) -> Result<(), JadeError> {
+        let name = self.names[slot].clone();
+        setField(&mut self.declared_mut(slot)?.0, &name, path, value)
+    }
+
+    // Add a new struct type
+    pub fn declare_struct(&mut self, name: String, fields: Vec<(String, String)>) {
+        self.structs.insert(name, fields);
+    }
+
+    // Get the fields of a struct type
+    pub fn get_struct(&self, name: &str) -> Option<&Vec<(String, String)>> {
+        self.structs.get(name)
+    }
+
+    // Add a new enum type
+    pub fn declare_enum(&mut self, name: String, variants: Vec<(String, Vec<String>)>) {
+        self.enums.insert(name, variants);
+    }
+
+    // Get the variants of an enum type
+    pub fn get_enum(&self, name: &str) -> Option<&Vec<(String, Vec<String>)>> {
+        self.enums.get(name)
+    }
+
+    // Struct and enum values report their own type name
+    pub fn getVarType (&self, slot: usize) -> Result<&String, JadeError> {
+        let var = self.global(slot).ok_or_else(|| self.unknown_variable(&self.names[slot]))?;
+        Ok(typeOf(&var.0, &var.1))
+    }
+
+    // Walk the struct definitions to find the declared type of a field
+    pub fn getFieldType (&self, slot: usize, path: &[String]) -> Result<String, JadeError> {
+        self.walkFieldType(self.getVarType(slot)?.clone(), path)
+    }
+
+    // The declared type of the field `path` leads to from a `varType`
+    pub fn walkFieldType (&self, varType: String, path: &[String]) -> Result<String, JadeError> {
+        let mut fieldType = varType;
+        for field in path {
+            let fields = self.get_struct(&fieldType).ok_or_else(|| fieldNotFoundError(&fieldType, field))?;
+            fieldType = fields
+                .iter()
+                .find(|(f, _)| f == field)
+                .ok_or_else(|| fieldNotFoundError(&fieldType, field))?
+                .1
+                .clone();
         }
+        Ok(fieldType)
     }
+}
 
-    pub fn getVarType (&self, name: &str) -> &String {
-        let varType = &self.variables.get(name).unwrap().1;
-        return varType;
+/// The type a variable holding `value` is checked against, its
+/// declared type except for structs and enums which report their own.
+pub fn typeOf<'a>(value: &'a Value, declared: &'a String) -> &'a String {
+    match value {
+        Value::Struct(typeName, _) | Value::Enum(typeName, _, _) => typeName,
+        _ => declared,
     }
 }
 
-pub fn jruntime(code: Vec<ParsedBlock>) {
+/// Appends to a variable holding `current`. Arrays and strings grow in
+/// place, a sink keeps its file name and the value goes in the file.
+pub fn appendTo(current: &mut Value, declared: &str, value: Value) -> Result<(), JadeError> {
+    match current {
+        Value::String(_) if declared == "sink" => appendToFile(current, &value),
+        Value::Array(values) => {
+            values.push(value);
+            Ok(())
+        }
+        Value::String(text) => {
+            text.push_str(&value.to_string());
+            Ok(())
+        }
+        other => Err(appendError(&other.type_name())),
+    }
+}
+
+/// Adds the value as a line at the end of a file, which is created if
+/// it isn't there.
+pub fn appendToFile(fileName: &Value, value: &Value) -> Result<(), JadeError> {
+    let Value::String(fileName) = fileName else {
+        return Err(valueError("string", fileName.clone()));
+    };
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(fileName)
+        .map_err(|error| writeFileError(fileName, error))?;
+    writeln!(file, "{}", value).map_err(|error| writeFileError(fileName, error))
+}
+
+/// Sets the field `path` leads to inside the variable `name` holding
+/// `target`.
+pub fn setField(target: &mut Value, name: &str, path: &[String], value: Value) -> Result<(), JadeError> {
+    let mut current: &mut Value = target;
+    for field in path {
+        current = match current {
+            Value::Struct(structName, fields) => {
+                match fields.iter_mut().find(|(f, _)| f == field) {
+                    Some((_, v)) => v,
+                    None => return Err(fieldNotFoundError(structName, field)),
+                }
+            }
+            _ => return Err(fieldNotFoundError(name, field)),
+        };
+    }
+
+    *current = value;
+    Ok(())
+}
+
+/// Resolves the program and runs it. Nothing runs if a variable can't
+/// be resolved.
+pub fn jruntime(mut code: Vec<Statement>) -> Result<(), Vec<JadeError>> {
     let mut variables = Runtime::new();
-    for block in code {
-        match block.blockType.as_str() {
-            "VarDec"    => {
-                variables.declare_variable(
-                    block.name.unwrap().to_string(),
-                    evaluate(&block.value.unwrap(), &variables, Some(block.datatype.clone().unwrap())),
-                    block.datatype.unwrap()
-                );
+    resolve(&mut code, &mut variables.names)?;
+    runStatements(&code, &mut variables)?;
+    Ok(())
+}
+
+// The slot the resolver gave a statement's variable. Code that wasn't
+// resolved can't find anything.
+fn resolved(slot: &Option<usize>, name: &str, variables: &Runtime) -> Result<usize, JadeError> {
+    slot.ok_or_else(|| variables.unknown_variable(name))
+}
+
+pub fn runStatements(code: &[Statement], variables: &mut Runtime) -> Result<(), JadeError> {
+    for statement in code {
+        // Anything that goes wrong points at the statement it came from
+        runStatement(statement, variables).map_err(|e| e.at(statement.span().clone()))?;
+    }
+    Ok(())
+}
+
+fn runStatement(statement: &Statement, variables: &mut Runtime) -> Result<(), JadeError> {
+    match statement {
+        Statement::VarDec { name, datatype, value: valueExpr, slot, .. } => {
+            let slot = resolved(slot, name, variables)?;
+            let value = evaluate(valueExpr, variables, None)?;
+            let datatype = match datatype {
+                Some(datatype) => datatype.clone(),
+                None => inferredType(&value).ok_or_else(|| inferNullError(name))?,
+            };
+            let value = coerce_to_type(value, &datatype).map_err(|e| e.at(valueExpr.span.clone()))?;
+            variables.declare_variable(slot, value, datatype);
+        }
+
+        Statement::StructDec { name, fields, .. } => {
+            variables.declare_struct(name.clone(), fields.clone());
+        }
+
+        Statement::EnumDec { name, variants, .. } => {
+            variables.declare_enum(name.clone(), variants.clone());
+        }
+
+        Statement::FieldSet { name, path, value, slot, .. } => {
+            let slot = resolved(slot, name, variables)?;
+            let fieldType = variables.getFieldType(slot, path)?;
+            let value = evaluate(value, variables, Some(fieldType))?;
+            variables.set_field(slot, path, value)?;
+        }
+
+        Statement::VarSet { name, value, slot, .. } => {
+            let slot = resolved(slot, name, variables)?;
+            let varType = variables.getVarType(slot)?.clone();
+            let value = evaluate(value, variables, Some(varType))?;
+            variables.set_variable(slot, value)?;
+        }
+        
+        Statement::Print { values, target, .. } => {
+            let values = values
+                .iter()
+                .map(|value| evaluate(value, variables, None))
+                .collect::<Result<Vec<Value>, JadeError>>()?;
+            let target = match target {
+                Some(target) => Some(evaluate(target, variables, Some(String::from("string")))?),
+                None => None,
+            };
+            variables.print_to(&values, target)?;
+        }
+
+        Statement::Append { value, target, .. } => {
+            let value = evaluate(value, variables, None)?;
+            match &target.kind {
+                // Only globals can be appended to, `match` bindings are
+                // just values
+                ExpressionKind::Variable { name, slot } => match slot {
+                    Some(Slot { depth: 0, index }) => variables.append(*index, value)?,
+                    Some(_) => return Err(appendError("a match binding")),
+                    None => return Err(variables.unknown_variable(name)),
+                },
+                _ => {
+                    let fileName = evaluate(target, variables, Some(String::from("string")))?;
+                    appendToFile(&fileName, &value)?;
+                }
             }
+        }
+
+        Statement::Read { name, source, slot, .. } => {
+            let slot = resolved(slot, name, variables)?;
+            let varType = variables.getVarType(slot)?.clone();
+            let source = match source {
+                Some(source) => Some(evaluate(source, variables, Some(String::from("string")))?),
+                None => None,
+            };
+            let value = variables.read_from(source)?;
+            variables.set_variable(slot, coerce_to_type(value, &varType)?)?;
+        }
 
-            "VarSet"    => {
-                variables.set_variable(block.name.clone().unwrap(), evaluate(&block.value.unwrap(), &variables, Some(variables.getVarType(block.name.unwrap().as_str()).clone())));
+        Statement::Expression { value, .. } => {
+            evaluate(value, variables, None)?;
+        }
+
+        Statement::If { condition, body, otherwise, .. } => {
+            if isTrue(condition, variables)? {
+                runStatements(body, variables)?;
+            } else if let Some(otherwise) = otherwise {
+                runStatements(otherwise, variables)?;
             }
-            
-            "PrintVar"  => {
-                // Print the value of a variable
-                let dtype: Option<&Value> = variables.get_variable(block.name.unwrap().as_str());
-                let printVar: &Value = dtype.clone().unwrap();
-
-                // Print the variable value
-                printStatement(printVar);
+        }
+
+        Statement::Unless { condition, body, .. } => {
+            if !isTrue(condition, variables)? {
+                runStatements(body, variables)?;
+            }
+        }
+
+        Statement::While { condition, body, .. } => {
+            while isTrue(condition, variables)? {
+                runStatements(body, variables)?;
+            }
+        }
+
+        Statement::Until { condition, body, .. } => {
+            while !isTrue(condition, variables)? {
+                runStatements(body, variables)?;
+            }
+        }
+
+        Statement::For { init, condition, step, body, .. } => {
+            if let Some(init) = init {
+                runStatement(init, variables)?;
             }
-            _ => {
-                // Handle other types of blocks, like control flow
+            while match condition {
+                Some(condition) => isTrue(condition, variables)?,
+                None => true,
+            } {
+                runStatements(body, variables)?;
+                if let Some(step) = step {
+                    runStatement(step, variables)?;
+                }
             }
         }
     }
+    Ok(())
+}
+
+// The type a variable declared without one gets from its value. The
+// checker infers the same thing from the value's expression.
+pub fn inferredType(value: &Value) -> Option<String> {
+    let datatype = match value {
+        Value::Int8(_) | Value::UInt8(_) | Value::Int16(_) | Value::UInt16(_) |
+        Value::Int32(_) | Value::UInt32(_) | Value::Int64(_) | Value::UInt64(_) => "int",
+        Value::Float(_) | Value::Float32(_) | Value::Float64(_) => "float",
+        Value::Bool(_) => "bool",
+        Value::String(_) => "string",
+        Value::Struct(name, _) | Value::Enum(name, _, _) => name,
+        Value::Null => return None,
+        _ => "$",
+    };
+    Some(datatype.to_string())
+}
+
+// Conditions have to be bools, nothing is truthy
+fn isTrue(condition: &Expression, variables: &mut Runtime) -> Result<bool, JadeError> {
+    match evaluate(condition, variables, Some(String::from("bool")))? {
+        Value::Bool(b) => Ok(b),
+        other => Err(valueError("bool", other)),
+    }
+}
+
+impl fmt::Display for Value {
+    // What `print` shows. Strings are bare at the top and quoted inside
+    // anything else, floats always keep enough digits to read back in.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int8(v) => write!(f, "{}", v),
+            Value::UInt8(v) => write!(f, "{}", v),
+            Value::Int16(v) => write!(f, "{}", v),
+            Value::UInt16(v) => write!(f, "{}", v),
+            Value::Int32(v) => write!(f, "{}", v),
+            Value::UInt32(v) => write!(f, "{}", v),
+            Value::Int64(v) => write!(f, "{}", v),
+            Value::UInt64(v) => write!(f, "{}", v),
+            Value::Float(v) | Value::Float64(v) => write!(f, "{:?}", v),
+            Value::Float32(v) => write!(f, "{:?}", v),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::String(text) => write!(f, "{}", text),
+            Value::Array(values) | Value::FixedArray(values, _) => write!(f, "[{}]", nested(values)),
+            Value::Slice(values) => write!(f, "[{}]", nested(values)),
+            Value::Pointer(value) => write!(f, "{}", Nested(value)),
+            Value::Map(entries) => {
+                // Sorted, so the same map always prints the same way
+                let mut entries: Vec<(&String, &Value)> = entries.iter().collect();
+                entries.sort_by_key(|(key, _)| *key);
+                let entries: Vec<String> = entries.iter().map(|(k, v)| format!("{}: {}", k, Nested(v))).collect();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
+            Value::Struct(name, fields) => {
+                let fields: Vec<String> = fields.iter().map(|(k, v)| format!("{}: {}", k, Nested(v))).collect();
+                write!(f, "{} {{ {} }}", name, fields.join(", "))
+            }
+            Value::Enum(name, variant, payload) if payload.is_empty() => write!(f, "{}::{}", name, variant),
+            Value::Enum(name, variant, payload) => write!(f, "{}::{}({})", name, variant, nested(payload)),
+            Value::Null => write!(f, "null"),
+        }
+    }
+}
+
+// A value inside another one, where strings keep their quotes
+struct Nested<'a>(&'a Value);
+
+impl fmt::Display for Nested<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Value::String(text) => write!(f, "{:?}", text),
+            other => write!(f, "{}", other),
+        }
+    }
+}
+
+fn nested(values: &[Value]) -> String {
+    values.iter().map(|value| Nested(value).to_string()).collect::<Vec<String>>().join(", ")
+}
+
+/// An output sink that keeps everything written to it. Clones share the
+/// same buffer, so one can go to `set_output` and the other be read.
+#[derive(Clone, Default)]
+pub struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Capture {
+    pub fn new() -> Self {
+        Capture::default()
+    }
+
+    /// Everything written so far.
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
 }
 
-fn printStatement(printVar: &Value) {
+impl Write for Capture {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lexCode;
+    use crate::parser::parseCode;
+
+    // Runs the code on a fresh runtime without type checking it first,
+    // so what comes back is the runtime's own error
+    fn run(code: &str) -> (Result<(), JadeError>, Runtime) {
+        let mut program = parseCode(lexCode(code.to_string(), "test.bg").unwrap()).unwrap();
+        let mut runtime = Runtime::new();
+        runtime.set_output(Capture::new());
+        resolve(&mut program, &mut runtime.names).unwrap();
+        let result = runStatements(&program, &mut runtime);
+        (result, runtime)
+    }
+
+    fn message(result: Result<(), JadeError>) -> String {
+        result.unwrap_err().diagnostic().message.clone()
+    }
 
-    println!("{:?}", printVar)
-}
\ No newline at end of file
+    #[test]
+    fn structsAreBuiltInDeclaredOrder() {
+        let (result, runtime) = run(
+            "struct Point { x int32; y int32; }
+            var p = Point { y = 2, x = 1 };",
+        );
+        result.unwrap();
+        assert_eq!(runtime.structs.get("Point").map(Vec::len), Some(2));
+        let point = Value::Struct("Point".into(), vec![("x".into(), Value::Int32(1)), ("y".into(), Value::Int32(2))]);
+        assert_eq!(runtime.get_variable("p"), Some(&point));
+    }
+
+    #[test]
+    fn fieldsCanBeReadAndWritten() {
+        let (result, runtime) = run(
+            "struct Point { x int32; y int32; }
+            struct Line { start Point; end Point; }
+            var l = Line { start = Point { x = 1, y = 2 }, end = Point { x = 3, y = 4 } };
+            var y int32 = l.start.y;
+            l.end.x = y + 10;",
+        );
+        result.unwrap();
+        assert_eq!(runtime.get_variable("y"), Some(&Value::Int32(2)));
+        let Some(Value::Struct(_, fields)) = runtime.get_variable("l") else { panic!("not a struct") };
+        let end = Value::Struct("Point".into(), vec![("x".into(), Value::Int32(12)), ("y".into(), Value::Int32(4))]);
+        assert_eq!(fields[1], ("end".into(), end));
+    }
+
+    #[test]
+    fn fieldsKeepTheirTypes() {
+        let declare = "struct Point { x int8; y int8; }\nvar p = Point { x = 1, y = 2 };\n";
+        let (result, _) = run(&format!("{}p.x = \"far\";", declare));
+        assert!(message(result).starts_with("Type mismatch! Expected: int8"));
+        let (result, runtime) = run(&format!("{}p.y = 300;", declare));
+        assert_eq!(message(result), "300 doesn't fit in int8");
+        let point = Value::Struct("Point".into(), vec![("x".into(), Value::Int8(1)), ("y".into(), Value::Int8(2))]);
+        assert_eq!(runtime.get_variable("p"), Some(&point));
+
+        let (result, _) = run(&format!("{}p.z = 1;", declare));
+        assert_eq!(message(result), "Struct 'Point' has no field 'z'");
+        let (result, _) = run("struct Point { x int8; y int8; }\nvar p = Point { x = 1 };");
+        assert_eq!(message(result), "Struct 'Point' is missing field 'y'");
+    }
+
+    #[test]
+    fn matchBindsEnumPayloads() {
+        let (result, runtime) = run(
+            "enum Shape { Dot, Circle(float), Rect(int32, int32) }
+            var s = Shape::Rect(3, 4);
+            var area int32 = match (s) { Shape::Rect(w, h) => w * h, Shape::Circle(_) => 0, Shape::Dot => 0 };
+            var dot = match (Shape::Dot) { Shape::Rect(w, h) => 1, Shape::Circle(r) => 2, Shape::Dot => 3 };",
+        );
+        result.unwrap();
+        assert_eq!(runtime.get_variable("area"), Some(&Value::Int32(12)));
+        assert_eq!(runtime.get_variable("dot"), Some(&Value::Int8(3)));
+    }
+
+    #[test]
+    fn matchTriesRangesThenTheWildcard() {
+        let grade = |score: i64| {
+            let (result, runtime) = run(&format!(
+                "var score = {};\nvar grade = match (score) {{ 90..=100 => \"A\", 80..90 => \"B\", _ => \"C\" }};",
+                score
+            ));
+            result.unwrap();
+            runtime.get_variable("grade").cloned()
+        };
+        assert_eq!(grade(100), Some(Value::from("A")));
+        assert_eq!(grade(90), Some(Value::from("A")));
+        assert_eq!(grade(89), Some(Value::from("B")));
+        assert_eq!(grade(80), Some(Value::from("B")));
+        assert_eq!(grade(12), Some(Value::from("C")));
+    }
+
+    #[test]
+    fn matchWithNoArmForTheValueFails() {
+        let (result, runtime) = run("var n = 7;\nvar word = match (n) { 1 => \"one\", 2..5 => \"few\" };");
+        assert_eq!(message(result), "No match arm for 7");
+        assert_eq!(runtime.get_variable("word"), None);
+        let (result, _) = run("enum C { A(int), B }\nvar c = C::A(1);\nvar n = match (c) { C::A(2) => 1, C::B => 2 };");
+        assert_eq!(message(result), "No match arm for C::A(1)");
+    }
+
+    #[test]
+    fn nullCoalescesAndSkipsFields() {
+        let (result, runtime) = run(
+            "struct Point { x int32; y int32?; }
+            var maybe Point? = null;
+            var limit int32? = null;
+            var a int32 = limit ?? 10;
+            limit = 3;
+            var b int32 = limit ?? 10;
+            var x int32? = maybe?.x;
+            maybe = Point { x = 5 };
+            var y int32 = maybe?.y ?? maybe?.x ?? 0;",
+        );
+        result.unwrap();
+        assert_eq!(runtime.get_variable("a"), Some(&Value::Int32(10)));
+        assert_eq!(runtime.get_variable("b"), Some(&Value::Int32(3)));
+        assert_eq!(runtime.get_variable("x"), Some(&Value::Null));
+        assert_eq!(runtime.get_variable("y"), Some(&Value::Int32(5)));
+    }
+
+    #[test]
+    fn nullIsOnlyAllowedWhereItsDeclared() {
+        let (result, _) = run("var n int32? = null;\nvar m int32 = n + 1;");
+        assert_eq!(message(result), "Can't use null with Add");
+        let (result, _) = run("var n int32 = null;");
+        assert!(message(result).starts_with("Type mismatch! Expected: int32"));
+        let (result, runtime) = run("var n int32 = 1;\nn = null;");
+        assert!(message(result).starts_with("Type mismatch! Expected: int32"));
+        assert_eq!(runtime.get_variable("n"), Some(&Value::Int32(1)));
+        let (result, _) = run("var n = null;");
+        assert_eq!(message(result), "Can't tell the type of 'n' from null");
+    }
+
+    #[test]
+    fn mixedWidthMathChecksTheWholeRightSide() {
+        let (result, _) = run("var n int8 = 5;\nvar t int = n + 300;");
+        assert_eq!(message(result), "Add overflowed with 5 and 300");
+        let (result, runtime) = run(
+            "var n int8 = 5;
+            var a int = n - 100 + 50;
+            var b int = n * 0;
+            var c int = n / 300;
+            var d int = n + 2.6;",
+        );
+        result.unwrap();
+        assert_eq!(runtime.get_variable("a"), Some(&Value::Int8(-45)));
+        assert_eq!(runtime.get_variable("b"), Some(&Value::Int8(0)));
+        assert_eq!(runtime.get_variable("c"), Some(&Value::Int8(0)));
+        assert_eq!(runtime.get_variable("d"), Some(&Value::Int8(8)));
+    }
+
+    #[test]
+    fn loopsRunTheRightNumberOfTimes() {
+        let (result, runtime) = run(
+            "var w int32 = 0;
+            while (w < 5) { w = w + 1; }
+            var u int32 = 0;
+            until (u >= 7) { u = u + 2; }
+            var f int32 = 0;
+            for (var i int32 = 0; i < 4; i = i + 1) { f = f + 10; }
+            var never int32 = 0;
+            while (false) { never = 1; }
+            until (true) { never = 2; }",
+        );
+        result.unwrap();
+        assert_eq!(runtime.get_variable("w"), Some(&Value::Int32(5)));
+        assert_eq!(runtime.get_variable("u"), Some(&Value::Int32(8)));
+        assert_eq!(runtime.get_variable("f"), Some(&Value::Int32(40)));
+        assert_eq!(runtime.get_variable("i"), Some(&Value::Int32(4)));
+        assert_eq!(runtime.get_variable("never"), Some(&Value::Int32(0)));
+    }
+
+    #[test]
+    fn branchesPickOneArm() {
+        let (result, runtime) = run(
+            "var a int32 = 0;
+            var b int32 = 0;
+            var c int32 = 0;
+            var d int32 = 0;
+            if (1 > 2) { a = 1; } elif (2 > 1) { a = 2; } else { a = 3; }
+            if (1 > 2) { b = 1; } elif (1 > 3) { b = 2; } else { b = 3; }
+            if (2 > 1) { c = 1; } elif (2 > 1) { c = 2; }
+            unless (1 > 2) { d = d + 1; }
+            unless (2 > 1) { d = d + 10; }",
+        );
+        result.unwrap();
+        assert_eq!(runtime.get_variable("a"), Some(&Value::Int32(2)));
+        assert_eq!(runtime.get_variable("b"), Some(&Value::Int32(3)));
+        assert_eq!(runtime.get_variable("c"), Some(&Value::Int32(1)));
+        assert_eq!(runtime.get_variable("d"), Some(&Value::Int32(1)));
+    }
+
+    #[test]
+    fn appendsOnlyGoToGlobals() {
+        let mut program = parseCode(lexCode("var log string = \"a\";\n\"b\" >> log;".to_string(), "test.bg").unwrap()).unwrap();
+        let mut runtime = Runtime::new();
+        resolve(&mut program, &mut runtime.names).unwrap();
+        runStatements(&program, &mut runtime).unwrap();
+        assert_eq!(runtime.get_variable("log"), Some(&Value::from("ab")));
+
+        // The same name, as if it were bound one scope in
+        let Statement::Append { target, .. } = &mut program[1] else { panic!("not an append") };
+        let ExpressionKind::Variable { slot, .. } = &mut target.kind else { panic!("not a variable") };
+        *slot = Some(Slot { depth: 1, index: 0 });
+        runtime.push_scope();
+        runtime.bind_variable(Value::from("x"));
+        assert!(message(runStatements(&program[1..], &mut runtime)).starts_with("Can't append to a match binding"));
+        assert_eq!(runtime.get_variable("log"), Some(&Value::from("ab")));
+    }
+}
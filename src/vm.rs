@@ -0,0 +1,334 @@
+use crate::compiler::{Chunk, Op};
+use crate::evaluator::{coerce_to_type, evaluate_binary_op, fieldValue, matchPattern, missingField, structFields, variantTypes};
+use crate::jadeErrors::{inferNullError, noMatchingArmError, suggest, valueError, variableNotFoundError, JadeError};
+use crate::runtime::{appendTo, appendToFile, inferredType, setField, typeOf, Runtime, Value};
+
+/// Runs a Chunk. Values are worked on in a stack, variables live in
+/// the slots the compiler gave them instead of being looked up by name.
+pub struct Vm<'a> {
+    chunk: &'a Chunk,
+    stack: Vec<Value>,
+    slots: Vec<Option<(Value, String)>>, // Value and type, `None` until declared
+    runtime: Runtime,                    // The structs and enums declared so far
+}
+
+impl<'a> Vm<'a> {
+    pub fn new(chunk: &'a Chunk) -> Self {
+        Vm {
+            chunk,
+            stack: Vec::new(),
+            slots: vec![None; chunk.slots.len()],
+            runtime: Runtime::new(),
+        }
+    }
+
+    /// A VM that carries on from `runtime`: its globals, structs and
+    /// enums are there from the start.
+    pub fn with_runtime(chunk: &'a Chunk, runtime: Runtime) -> Self {
+        let mut vm = Vm { runtime, ..Vm::new(chunk) };
+        for (name, slot) in &chunk.globals {
+            if let Some(index) = vm.runtime.slot_of(name) {
+                vm.slots[*slot] = vm.runtime.global(index).cloned();
+            }
+        }
+        vm
+    }
+
+    /// Hands back the runtime with the globals as the program left them.
+    pub fn into_runtime(self) -> Runtime {
+        let mut runtime = self.runtime;
+        for (name, slot) in &self.chunk.globals {
+            if let Some((value, datatype)) = &self.slots[*slot] {
+                let index = runtime.slot_for(name);
+                runtime.declare_variable(index, value.clone(), datatype.clone());
+            }
+        }
+        runtime
+    }
+
+    pub fn run(&mut self) -> Result<(), JadeError> {
+        let mut pc: usize = 0;
+        while pc < self.chunk.code.len() {
+            let at = pc;
+            pc += 1;
+            // Anything that goes wrong points at where the instruction
+            // came from
+            self.step(at, &mut pc).map_err(|e| e.at(self.chunk.spans[at].clone()))?;
+        }
+        Ok(())
+    }
+
+    /// The value of the variable `name` has once the program has run.
+    pub fn global(&self, name: &str) -> Option<&Value> {
+        self.chunk.globals.get(name).and_then(|slot| self.slots[*slot].as_ref()).map(|(value, _)| value)
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("the compiler keeps the stack balanced")
+    }
+
+    // The variable in a slot, or the same error the runtime gives
+    fn variable(&self, slot: usize) -> Result<&(Value, String), JadeError> {
+        self.slots[slot].as_ref().ok_or_else(|| {
+            let name = &self.chunk.slots[slot];
+            let declared = self.chunk.slots.iter().zip(&self.slots).filter(|(_, v)| v.is_some()).map(|(n, _)| n);
+            suggest(variableNotFoundError(name), name, declared)
+        })
+    }
+
+    fn step(&mut self, at: usize, pc: &mut usize) -> Result<(), JadeError> {
+        let chunk = self.chunk;
+        match &chunk.code[at] {
+            Op::Constant(index) => self.stack.push(chunk.constants[*index].clone()),
+
+            Op::Load(slot) => {
+                let value = self.variable(*slot)?.0.clone();
+                self.stack.push(value);
+            }
+
+            Op::Declare(slot, datatype) => {
+                let value = self.pop();
+                let name = &chunk.slots[*slot];
+                let (value, datatype) = match datatype {
+                    Some(datatype) => (value, chunk.types[*datatype].clone()),
+                    None => {
+                        let datatype = inferredType(&value).ok_or_else(|| inferNullError(name))?;
+                        (coerce_to_type(value, &datatype)?, datatype)
+                    }
+                };
+                self.slots[*slot] = Some((value, datatype));
+            }
+
+            Op::Check(slot) => {
+                self.variable(*slot)?;
+            }
+
+            Op::Store(slot) => {
+                let value = self.pop();
+                let (current, declared) = self.variable(*slot)?;
+                let value = coerce_to_type(value, typeOf(current, declared))?;
+                if let Some((current, _)) = &mut self.slots[*slot] {
+                    *current = value;
+                }
+            }
+
+            Op::CheckField(slot, path) => {
+                let (current, declared) = self.variable(*slot)?;
+                self.runtime.walkFieldType(typeOf(current, declared).clone(), &chunk.paths[*path])?;
+            }
+
+            Op::StoreField(slot, path) => {
+                let value = self.pop();
+                let path = &chunk.paths[*path];
+                let (current, declared) = self.variable(*slot)?;
+                let fieldType = self.runtime.walkFieldType(typeOf(current, declared).clone(), path)?;
+                let value = coerce_to_type(value, &fieldType)?;
+                if let Some((current, _)) = &mut self.slots[*slot] {
+                    setField(current, &chunk.slots[*slot], path, value)?;
+                }
+            }
+
+            Op::Coerce(datatype) => {
+                let value = self.pop();
+                self.stack.push(coerce_to_type(value, &chunk.types[*datatype])?);
+            }
+
+            Op::Binary(op) => {
+                let right = self.pop();
+                let left = self.pop();
+                self.stack.push(evaluate_binary_op(op, left, right)?);
+            }
+
+            Op::Field(field, optional) => {
+                let object = self.pop();
+                self.stack.push(fieldValue(object, &chunk.names[*field], *optional)?);
+            }
+
+            Op::Struct(literal) => {
+                let literal = &chunk.structLiterals[*literal];
+                let given = self.stack.split_off(self.stack.len() - literal.fields.len());
+                let declared = structFields(&self.runtime, &literal.name, literal.fields.iter())?;
+
+                let mut values: Vec<(String, Value)> = Vec::new();
+                for (field, fieldType) in &declared {
+                    let value = match literal.fields.iter().position(|f| f == field) {
+                        Some(index) if literal.coerced => given[index].clone(),
+                        Some(index) => coerce_to_type(given[index].clone(), fieldType)?,
+                        None => missingField(&literal.name, field, fieldType)?,
+                    };
+                    values.push((field.clone(), value));
+                }
+                self.stack.push(Value::Struct(literal.name.clone(), values));
+            }
+
+            Op::Variant(variant) => {
+                let (enumName, variant, count) = &chunk.variants[*variant];
+                let types = variantTypes(&self.runtime, enumName, variant, *count)?;
+                let payload = self.stack.split_off(self.stack.len() - count);
+                let payload = payload
+                    .into_iter()
+                    .zip(&types)
+                    .map(|(value, valueType)| coerce_to_type(value, valueType))
+                    .collect::<Result<Vec<Value>, JadeError>>()?;
+                self.stack.push(Value::Enum(enumName.clone(), variant.clone(), payload));
+            }
+
+            Op::MatchArm(arm, target) => {
+                let (pattern, slots) = &chunk.arms[*arm];
+                let subject = self.stack.last().expect("the compiler keeps the stack balanced");
+                let mut bindings: Vec<(String, Value)> = Vec::new();
+                if matchPattern(pattern, subject, &mut bindings) {
+                    self.pop();
+                    for (slot, (_, value)) in slots.iter().zip(bindings) {
+                        self.slots[*slot] = Some((value, String::from("$")));
+                    }
+                } else {
+                    *pc = *target;
+                }
+            }
+
+            Op::NoMatch => {
+                let subject = self.pop();
+                return Err(noMatchingArmError(&subject));
+            }
+
+            Op::Jump(target) => *pc = *target,
+
+            Op::JumpIfFalse(target) | Op::JumpIfTrue(target) => {
+                // Conditions have to be bools, nothing is truthy
+                let condition = match self.pop() {
+                    Value::Bool(b) => b,
+                    other => return Err(valueError("bool", other)),
+                };
+                if condition == matches!(chunk.code[at], Op::JumpIfTrue(_)) {
+                    *pc = *target;
+                }
+            }
+
+            Op::JumpIfNotNull(target) => {
+                if matches!(self.stack.last(), Some(Value::Null)) {
+                    self.pop();
+                } else {
+                    *pc = *target;
+                }
+            }
+
+            Op::Print(count, toFile) => {
+                let target = if *toFile { Some(self.pop()) } else { None };
+                let values = self.stack.split_off(self.stack.len() - count);
+                self.runtime.print_to(&values, target)?;
+            }
+
+            Op::Append(slot) => {
+                let value = self.pop();
+                self.variable(*slot)?;
+                if let Some((current, declared)) = &mut self.slots[*slot] {
+                    appendTo(current, declared, value)?;
+                }
+            }
+
+            Op::AppendFile => {
+                let fileName = self.pop();
+                let value = self.pop();
+                appendToFile(&fileName, &value)?;
+            }
+
+            Op::Read(slot, fromFile) => {
+                let source = if *fromFile { Some(self.pop()) } else { None };
+                let declared = self.variable(*slot)?.1.clone();
+                let value = coerce_to_type(self.runtime.read_from(source)?, &declared)?;
+                if let Some((current, _)) = &mut self.slots[*slot] {
+                    *current = value;
+                }
+            }
+
+            Op::Call(name, count) => {
+                let args = self.stack.split_off(self.stack.len() - count);
+                let value = self.runtime.call(&chunk.names[*name], args)?;
+                self.stack.push(value);
+            }
+
+            Op::Pop => {
+                self.pop();
+            }
+
+            Op::DeclareStruct(index) => {
+                let (name, fields) = &chunk.structs[*index];
+                self.runtime.declare_struct(name.clone(), fields.clone());
+            }
+
+            Op::DeclareEnum(index) => {
+                let (name, variants) = &chunk.enums[*index];
+                self.runtime.declare_enum(name.clone(), variants.clone());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile;
+    use crate::lexer::lexCode;
+    use crate::parser::parseCode;
+    use crate::resolver::resolve;
+    use crate::runtime::runStatements;
+
+    // Runs the code on both engines and checks every variable in `names`
+    // ends up the same, or that both fail with the same error
+    fn sameAsRuntime(code: &str, names: &[&str]) {
+        let mut program = parseCode(lexCode(code.to_string(), "test.bg").unwrap()).unwrap();
+
+        let mut runtime = Runtime::new();
+        resolve(&mut program, &mut runtime.names).unwrap();
+        let treeResult = runStatements(&program, &mut runtime);
+
+        let chunk = compile(&program);
+        let mut vm = Vm::new(&chunk);
+        let vmResult = vm.run();
+
+        assert_eq!(vmResult, treeResult);
+        for name in names {
+            assert_eq!(vm.global(name), runtime.get_variable(name), "`{}` differs", name);
+        }
+    }
+
+    #[test]
+    fn loopsAndBranches() {
+        sameAsRuntime(
+            "var total int32 = 0;
+            for (var i int32 = 0; i < 100; i = i + 1) {
+                if (i > 50) { total = total + 2; } elif (i == 7) { total = total - 1; } else { total = total + 1; }
+            }
+            var n = 0;
+            until (n >= 10) { n = n + 3; }
+            unless (n == 12) { n = 0; }",
+            &["total", "i", "n"],
+        );
+    }
+
+    #[test]
+    fn structsEnumsAndMatches() {
+        sameAsRuntime(
+            "struct Point { x int; y int?; }
+            enum Shape { Circle(int), Rect(int, int), Empty }
+            var p = Point { y = 4, x = 1 };
+            p.x = 10;
+            var s Shape = Shape::Rect(3, 4);
+            var area = match (s) { Shape::Circle(r) => r * r * 3, Shape::Rect(w, h) => w * h, _ => 0 };
+            var w = match (p.y ?? 0) { 0..4 => \"small\", _ => \"big\" };
+            var q = p?.y ?? 7;",
+            &["p", "s", "area", "w", "q"],
+        );
+    }
+
+    #[test]
+    fn failsTheSameWay() {
+        sameAsRuntime("var a int8 = 100;\nwhile (true) { a = a + 10; }", &["a"]);
+        sameAsRuntime("var a int8 = 1;\nvar b int8 = a / 0;", &["a"]);
+        sameAsRuntime("var a int8 = 5;\nvar b int = a + 300;", &["a"]);
+        sameAsRuntime("var a int8 = 300;", &[]);
+        sameAsRuntime("var a = 1;\nif (a) { a = 2; }", &["a"]);
+    }
+}